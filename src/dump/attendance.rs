@@ -0,0 +1,113 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::wilma::models::{AttendanceMark, AttendanceMarkType, Period};
+
+#[derive(Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+pub fn dump_to_writer(marks: &[AttendanceMark], writer: impl Write, format: Format) -> Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer(writer, marks)?;
+        }
+        Format::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            for m in marks {
+                csv.serialize(m)?;
+            }
+            csv.flush()?;
+        }
+    };
+
+    Ok(())
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct AttendanceSummary {
+    pub name: String,
+    pub excused: usize,
+    pub unexcused: usize,
+    pub late: usize,
+    pub other: usize,
+}
+
+impl AttendanceSummary {
+    fn add(&mut self, mark: &AttendanceMark) {
+        match mark.mark_type {
+            AttendanceMarkType::Excused => self.excused += 1,
+            AttendanceMarkType::Unexcused => self.unexcused += 1,
+            AttendanceMarkType::Late => self.late += 1,
+            AttendanceMarkType::Other => self.other += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.excused + self.unexcused + self.late + self.other
+    }
+}
+
+fn summarize<'a>(
+    marks: &'a [AttendanceMark],
+    key: impl Fn(&'a AttendanceMark) -> String,
+) -> Vec<AttendanceSummary> {
+    let mut summaries: Vec<AttendanceSummary> = vec![];
+    for mark in marks {
+        let name = key(mark);
+        match summaries.iter_mut().find(|s| s.name == name) {
+            Some(summary) => summary.add(mark),
+            None => {
+                let mut summary = AttendanceSummary {
+                    name,
+                    ..Default::default()
+                };
+                summary.add(mark);
+                summaries.push(summary);
+            }
+        }
+    }
+    summaries
+}
+
+/// Marks per course code, falling back to the course name when there is no code
+pub fn summarize_by_course(marks: &[AttendanceMark]) -> Vec<AttendanceSummary> {
+    let mut summaries = summarize(marks, |m| {
+        m.course_code.clone().unwrap_or_else(|| m.course.clone())
+    });
+    summaries.sort_by(|a, b| a.name.cmp(&b.name));
+    summaries
+}
+
+/// Period names repeat every school year, so they get their dates when marks span several
+fn period_label(period: &Period, periods: &[Period]) -> String {
+    if periods.iter().filter(|p| p.name == period.name).count() > 1 {
+        format!("{} ({} - {})", period.name, period.start, period.end)
+    } else {
+        period.name.clone()
+    }
+}
+
+/// Marks per period in period order, marks outside every period are counted last
+pub fn summarize_by_period(marks: &[AttendanceMark], periods: &[Period]) -> Vec<AttendanceSummary> {
+    let labels: Vec<String> = periods.iter().map(|p| period_label(p, periods)).collect();
+
+    let mut summaries = summarize(marks, |m| {
+        periods
+            .iter()
+            .position(|p| (p.start..=p.end).contains(&m.date))
+            .map_or_else(|| String::from("Outside periods"), |i| labels[i].clone())
+    });
+    summaries.sort_by_key(|s| {
+        labels
+            .iter()
+            .position(|l| *l == s.name)
+            .unwrap_or(labels.len())
+    });
+    summaries
+}
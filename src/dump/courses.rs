@@ -1,23 +1,15 @@
 use anyhow::Result;
+use clap::ValueEnum;
 use std::io::Write;
 
 use crate::wilma::models::Course;
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Debug, ValueEnum)]
 pub enum Format {
     Json,
     Csv,
 }
 
-impl ToString for Format {
-    fn to_string(&self) -> String {
-        match self {
-            Format::Json => "json".to_string(),
-            Format::Csv => "csv".to_string(),
-        }
-    }
-}
-
 pub fn dump_to_writer(courses: &Vec<Course>, writer: impl Write, format: Format) -> Result<()> {
     match format {
         Format::Json => {
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use std::io::Write;
+
+use crate::wilma::models::Exam;
+
+#[derive(Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+pub fn dump_to_writer(exams: &[Exam], writer: impl Write, format: Format) -> Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer(writer, exams)?;
+        }
+        Format::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            for e in exams {
+                csv.serialize(e)?;
+            }
+            csv.flush()?;
+        }
+    };
+
+    Ok(())
+}
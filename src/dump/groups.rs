@@ -0,0 +1,60 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::wilma::models::TeachingGroup;
+
+#[derive(Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+//one row per enrolled student so the file works as a spreadsheet as is
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct StudentRow<'a> {
+    group: &'a str,
+    course_code: Option<&'a str>,
+    course: &'a str,
+    period: Option<&'a str>,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    student: Option<&'a str>,
+    class: Option<&'a str>,
+}
+
+pub fn dump_to_writer(groups: &[TeachingGroup], writer: impl Write, format: Format) -> Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer(writer, groups)?;
+        }
+        Format::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            for g in groups {
+                //groups without students still get a row
+                let students = match g.students.is_empty() {
+                    true => vec![None],
+                    false => g.students.iter().map(Some).collect(),
+                };
+                for s in students {
+                    csv.serialize(StudentRow {
+                        group: &g.code,
+                        course_code: g.course_code.as_deref(),
+                        course: &g.course,
+                        period: g.period.as_deref(),
+                        start: g.start,
+                        end: g.end,
+                        student: s.map(|s| s.name.as_str()),
+                        class: s.and_then(|s| s.class.as_deref()),
+                    })?;
+                }
+            }
+            csv.flush()?;
+        }
+    };
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use std::io::Write;
+
+use crate::wilma::models::{HomeworkEntry, HomeworkKind};
+
+#[derive(Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+    #[value(name = "md", alias = "markdown")]
+    Markdown,
+}
+
+//one heading per day, entries are expected to be sorted by date already
+fn write_markdown(entries: &[HomeworkEntry], mut writer: impl Write) -> Result<()> {
+    writeln!(writer, "# Homework and lesson diary")?;
+
+    let mut date = None;
+    for entry in entries {
+        if date != Some(entry.date) {
+            writeln!(writer)?;
+            writeln!(writer, "## {}", entry.date)?;
+            writeln!(writer)?;
+            date = Some(entry.date);
+        }
+
+        let kind = match entry.kind {
+            HomeworkKind::Homework => "Homework",
+            HomeworkKind::Diary => "Diary",
+        };
+        write!(writer, "- **{}** {kind}", entry.group)?;
+        if !entry.course.is_empty() {
+            write!(writer, ", {}", entry.course)?;
+        }
+        if let Some(teacher) = &entry.teacher {
+            write!(writer, " ({teacher})")?;
+        }
+        writeln!(writer)?;
+        for line in entry.text.lines().filter(|l| !l.trim().is_empty()) {
+            writeln!(writer, "  {}", line.trim())?;
+        }
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn dump_to_writer(entries: &[HomeworkEntry], writer: impl Write, format: Format) -> Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer(writer, entries)?;
+        }
+        Format::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            for e in entries {
+                csv.serialize(e)?;
+            }
+            csv.flush()?;
+        }
+        Format::Markdown => {
+            write_markdown(entries, writer)?;
+        }
+    };
+
+    Ok(())
+}
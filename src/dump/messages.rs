@@ -0,0 +1,245 @@
+use anyhow::{anyhow, bail, Result};
+use base64_url::base64;
+use chrono::{NaiveDateTime, TimeZone};
+use chrono_tz::Europe::Helsinki;
+use clap::ValueEnum;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::wilma::{
+    models::{Message, MessageFolder},
+    Wilma,
+};
+
+#[derive(Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    Json,
+    Mbox,
+    Maildir,
+}
+
+/// Separates the maildir file name from its flags. `:` isn't allowed in Windows file names, so
+/// use `!` like other Windows maildir tools
+#[cfg(windows)]
+const INFO_SEPARATOR: char = '!';
+#[cfg(not(windows))]
+const INFO_SEPARATOR: char = ':';
+
+/// A message or one of its replies as a single email
+struct Mail<'a> {
+    message_id: String,
+    in_reply_to: Option<String>,
+    folder: MessageFolder,
+    subject: &'a str,
+    sender: &'a str,
+    recipients: &'a [String],
+    timestamp: NaiveDateTime,
+    body_html: &'a str,
+}
+
+fn message_id(host: &str, id: i64, reply: Option<i64>) -> String {
+    match reply {
+        Some(reply) => format!("<wilma-message-{id}-reply-{reply}@{host}>"),
+        None => format!("<wilma-message-{id}@{host}>"),
+    }
+}
+
+fn mails<'a>(messages: &'a [Message], host: &str) -> Vec<Mail<'a>> {
+    let mut mails = vec![];
+    for message in messages {
+        let parent = message_id(host, message.id, None);
+        mails.push(Mail {
+            message_id: parent.clone(),
+            in_reply_to: None,
+            folder: message.folder,
+            subject: &message.subject,
+            sender: &message.sender,
+            recipients: &message.recipients,
+            timestamp: message.timestamp,
+            body_html: message.body_html.as_deref().unwrap_or_default(),
+        });
+        for reply in &message.replies {
+            mails.push(Mail {
+                message_id: message_id(host, message.id, Some(reply.id)),
+                in_reply_to: Some(parent.clone()),
+                folder: message.folder,
+                subject: &message.subject,
+                sender: &reply.sender,
+                recipients: &[],
+                timestamp: reply.timestamp,
+                body_html: &reply.body_html,
+            });
+        }
+    }
+    mails
+}
+
+/// Encodes a header value as RFC 2047 encoded words when it isn't plain ascii
+fn encode_header(value: &str) -> String {
+    if value.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+        return value.to_string();
+    }
+
+    //45 bytes of input keeps each encoded word under the 75 character limit
+    let mut words = vec![];
+    let mut chunk = String::new();
+    for c in value.chars() {
+        if chunk.len() + c.len_utf8() > 45 {
+            words.push(format!("=?UTF-8?B?{}?=", base64::encode(&chunk)));
+            chunk.clear();
+        }
+        chunk.push(c);
+    }
+    if !chunk.is_empty() {
+        words.push(format!("=?UTF-8?B?{}?=", base64::encode(&chunk)));
+    }
+    words.join("\n ")
+}
+
+fn encode_address(name: &str, host: &str) -> String {
+    //wilma doesn't expose addresses, names are all we have. Control characters like CR and LF
+    //only get through encoded, so a name can't add headers
+    if name.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ') {
+        format!("{name} <noreply@{host}>")
+    } else if name.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+        format!(
+            "\"{}\" <noreply@{host}>",
+            name.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+    } else {
+        format!("{} <noreply@{host}>", encode_header(name))
+    }
+}
+
+fn folder_name(folder: MessageFolder) -> &'static str {
+    match folder {
+        MessageFolder::Inbox => "Inbox",
+        MessageFolder::Sent => "Sent",
+        MessageFolder::Archive => "Archive",
+    }
+}
+
+fn write_mail(mail: &Mail, host: &str, mut writer: impl Write) -> Result<()> {
+    let date = Helsinki
+        .from_local_datetime(&mail.timestamp)
+        .earliest()
+        .ok_or_else(|| anyhow!("Invalid local time {}", mail.timestamp))?;
+
+    writeln!(writer, "Message-ID: {}", mail.message_id)?;
+    if let Some(parent) = &mail.in_reply_to {
+        writeln!(writer, "In-Reply-To: {parent}")?;
+        writeln!(writer, "References: {parent}")?;
+    }
+    writeln!(writer, "Date: {}", date.to_rfc2822())?;
+    writeln!(writer, "From: {}", encode_address(mail.sender, host))?;
+    if !mail.recipients.is_empty() {
+        let recipients = mail
+            .recipients
+            .iter()
+            .map(|r| encode_address(r, host))
+            .collect::<Vec<_>>()
+            .join(",\n ");
+        writeln!(writer, "To: {recipients}")?;
+    }
+    let subject = match mail.in_reply_to {
+        Some(_) => format!("Re: {}", mail.subject),
+        None => mail.subject.to_string(),
+    };
+    writeln!(writer, "Subject: {}", encode_header(&subject))?;
+    writeln!(writer, "X-Wilma-Folder: {}", folder_name(mail.folder))?;
+    writeln!(writer, "MIME-Version: 1.0")?;
+    writeln!(writer, "Content-Type: text/html; charset=utf-8")?;
+    writeln!(writer, "Content-Transfer-Encoding: 8bit")?;
+    writeln!(writer)?;
+    for line in mail.body_html.lines() {
+        writeln!(writer, "{line}")?;
+    }
+
+    Ok(())
+}
+
+/// mboxrd, lines looking like a "From " separator get quoted with an extra '>'
+fn write_mbox(messages: &[Message], host: &str, mut writer: impl Write) -> Result<()> {
+    for mail in mails(messages, host) {
+        let mut buffer = vec![];
+        write_mail(&mail, host, &mut buffer)?;
+
+        writeln!(
+            writer,
+            "From {} {}",
+            env!("CARGO_PKG_NAME"),
+            mail.timestamp.format("%a %b %e %H:%M:%S %Y")
+        )?;
+        for line in String::from_utf8(buffer)?.lines() {
+            if line.trim_start_matches('>').starts_with("From ") {
+                write!(writer, ">")?;
+            }
+            writeln!(writer, "{line}")?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn get_host(wilma: &Wilma) -> Result<&str> {
+    wilma
+        .base_url
+        .host_str()
+        .ok_or_else(|| anyhow!("Wilma url has no host"))
+}
+
+pub fn dump_to_writer(
+    messages: &[Message],
+    wilma: &Wilma,
+    writer: impl Write,
+    format: Format,
+) -> Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer(writer, messages)?;
+        }
+        Format::Mbox => {
+            write_mbox(messages, get_host(wilma)?, writer)?;
+        }
+        Format::Maildir => bail!("Maildir is a directory tree, use dump_to_maildir"),
+    };
+
+    Ok(())
+}
+
+/// Writes a Maildir++ tree, the inbox is the root and other folders are `.Sent` and `.Archive`
+///
+/// File names only depend on the message so dumping again overwrites instead of duplicating.
+pub fn dump_to_maildir(messages: &[Message], wilma: &Wilma, path: &Path) -> Result<()> {
+    let host = get_host(wilma)?;
+
+    for mail in mails(messages, host) {
+        let folder = match mail.folder {
+            MessageFolder::Inbox => path.to_path_buf(),
+            folder => path.join(format!(".{}", folder_name(folder))),
+        };
+        for dir in ["cur", "new", "tmp"] {
+            fs::create_dir_all(folder.join(dir))?;
+        }
+
+        let id = mail
+            .message_id
+            .trim_matches(|c| c == '<' || c == '>')
+            .replace('@', ".");
+        let name = format!(
+            "{}.{}{INFO_SEPARATOR}2,S",
+            mail.timestamp.and_utc().timestamp(),
+            id
+        );
+
+        //write to tmp first and move into place, as maildir readers expect
+        let tmp = folder.join("tmp").join(&name);
+        write_mail(&mail, host, fs::File::create(&tmp)?)?;
+        fs::rename(tmp, folder.join("cur").join(name))?;
+    }
+
+    Ok(())
+}
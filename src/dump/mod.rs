@@ -1 +1,19 @@
-pub mod courses;
+use clap::ValueEnum;
+
+pub mod attendance;
+pub mod courses;
+pub mod exams;
+pub mod groups;
+pub mod homework;
+pub mod messages;
+pub mod news;
+pub mod schedule;
+pub mod tray;
+
+/// `--format` name of a dump format, also used as the file extension
+pub fn format_name(format: &impl ValueEnum) -> String {
+    format
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,170 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use lazy_static::lazy_static;
+use reqwest::Client;
+use scraper::{Html, Node, Selector};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::wilma::{
+    download::{self, AttachmentParent, Downloader, ManifestEntry},
+    models::{NewsItem, NewsScope},
+    Wilma,
+};
+
+const ATTACHMENT_DIR: &str = "attachments";
+
+lazy_static! {
+    static ref UNSAFE_ELEMENTS: Selector =
+        Selector::parse("script, style, iframe, frame, object, embed, form").unwrap();
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    Json,
+    #[value(name = "md", alias = "markdown")]
+    Markdown,
+}
+
+pub fn dump_to_writer(items: &[NewsItem], writer: impl Write, format: Format) -> Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer(writer, items)?;
+        }
+        Format::Markdown => bail!("Markdown is one file per item, use dump_to_directory"),
+    };
+
+    Ok(())
+}
+
+fn item_name(item: &NewsItem) -> String {
+    let date = item
+        .published
+        .map_or_else(|| String::from("undated"), |d| d.to_string());
+    format!("{date}-{}", item.id)
+}
+
+/// Drops scripts, embedded content and event handler attributes from Wilma's html, the rest is
+/// kept as is
+fn sanitize_html(html: &str) -> String {
+    let mut fragment = Html::parse_fragment(html);
+
+    let unsafe_ids: Vec<_> = fragment.select(&UNSAFE_ELEMENTS).map(|e| e.id()).collect();
+    for id in unsafe_ids {
+        if let Some(mut node) = fragment.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+
+    let ids: Vec<_> = fragment.tree.nodes().map(|n| n.id()).collect();
+    for id in ids {
+        if let Some(Node::Element(element)) = fragment.tree.get_mut(id).as_mut().map(|n| n.value())
+        {
+            element.attrs.retain(|name, value| {
+                !name.local.starts_with("on")
+                    && !value.trim_start().to_lowercase().starts_with("javascript:")
+            });
+        }
+    }
+
+    fragment.root_element().inner_html()
+}
+
+fn write_markdown(
+    item: &NewsItem,
+    attachments: &[&ManifestEntry],
+    mut writer: impl Write,
+) -> Result<()> {
+    writeln!(writer, "# {}", item.title)?;
+    writeln!(writer)?;
+
+    let mut meta = vec![];
+    if let Some(author) = &item.author {
+        meta.push(author.clone());
+    }
+    if let Some(published) = item.published {
+        meta.push(published.to_string());
+    }
+    meta.push(
+        match item.scope {
+            NewsScope::School => "School news",
+            NewsScope::Role => "Personal news",
+        }
+        .to_string(),
+    );
+    writeln!(writer, "*{}*", meta.join(", "))?;
+    writeln!(writer)?;
+
+    //markdown allows raw html, converting wilma's formatting would only lose information
+    if let Some(body) = &item.body_html {
+        writeln!(
+            writer,
+            "<!-- html from Wilma, scripts and embedded content removed -->"
+        )?;
+        writeln!(writer, "{}", sanitize_html(body.trim()))?;
+        writeln!(writer)?;
+    }
+
+    if !attachments.is_empty() {
+        writeln!(writer, "## Attachments")?;
+        writeln!(writer)?;
+        for attachment in attachments {
+            writeln!(
+                writer,
+                "- [{}](<{ATTACHMENT_DIR}/{}>)",
+                attachment.name, attachment.file
+            )?;
+        }
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes `<date>-<id>.md` for every item, attachments go to a shared `attachments` directory
+/// with a manifest so they are only stored once
+pub async fn dump_to_directory(
+    items: &[NewsItem],
+    client: &Client,
+    wilma: &Wilma,
+    path: &Path,
+    download_limit: usize,
+) -> Result<()> {
+    fs::create_dir_all(path)?;
+
+    let downloader = Downloader::new(path.join(ATTACHMENT_DIR), download_limit);
+    let downloaded = downloader
+        .download(client, wilma, download::news_attachments(items))
+        .await?;
+
+    for item in items {
+        let attachments = downloaded
+            .iter()
+            .filter(|e| e.parent == AttachmentParent::News(item.id))
+            .collect::<Vec<_>>();
+
+        write_markdown(
+            item,
+            &attachments,
+            fs::File::create(path.join(format!("{}.md", item_name(item))))?,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_removes_scripts_and_handlers() {
+        let html = r#"<p onclick="steal()">Hello <b>all</b></p><script>alert(1)</script><a href="javascript:alert(1)">x</a><a href="https://example.com/">y</a>"#;
+
+        assert_eq!(
+            sanitize_html(html),
+            r#"<p>Hello <b>all</b></p><a>x</a><a href="https://example.com/">y</a>"#
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, NaiveTime, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::wilma::{models::Lesson, Wilma};
+
+const TIMEZONE: &str = "Europe/Helsinki";
+
+//EET/EEST with the EU transition rules, Wilma times are always local Finnish time
+const VTIMEZONE: &[&str] = &[
+    "BEGIN:VTIMEZONE",
+    "TZID:Europe/Helsinki",
+    "BEGIN:STANDARD",
+    "DTSTART:19701025T040000",
+    "TZOFFSETFROM:+0300",
+    "TZOFFSETTO:+0200",
+    "TZNAME:EET",
+    "RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU",
+    "END:STANDARD",
+    "BEGIN:DAYLIGHT",
+    "DTSTART:19700329T030000",
+    "TZOFFSETFROM:+0200",
+    "TZOFFSETTO:+0300",
+    "TZNAME:EEST",
+    "RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU",
+    "END:DAYLIGHT",
+    "END:VTIMEZONE",
+];
+
+#[derive(Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+    Ics,
+}
+
+//csv can't serialize nested lists, so they are joined into single columns
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct LessonRow<'a> {
+    date: NaiveDate,
+    weekday: u32,
+    start: NaiveTime,
+    end: NaiveTime,
+    subject: &'a str,
+    course_codes: String,
+    teachers: String,
+    rooms: String,
+    groups: String,
+}
+
+impl<'a> From<&'a Lesson> for LessonRow<'a> {
+    fn from(lesson: &'a Lesson) -> Self {
+        LessonRow {
+            date: lesson.date,
+            weekday: lesson.weekday,
+            start: lesson.start,
+            end: lesson.end,
+            subject: &lesson.subject,
+            course_codes: lesson.course_codes.join(", "),
+            teachers: lesson.teachers.join(", "),
+            rooms: lesson.rooms.join(", "),
+            groups: lesson.groups.join(", "),
+        }
+    }
+}
+
+/// Escapes a TEXT value, RFC 5545 3.3.11
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Writes a content line folded to 75 octets, RFC 5545 3.1
+fn write_line(writer: &mut impl Write, line: &str) -> Result<()> {
+    let mut start = 0;
+    let mut limit = 75;
+    for (i, c) in line.char_indices() {
+        if i + c.len_utf8() - start > limit {
+            writer.write_all(&line.as_bytes()[start..i])?;
+            writer.write_all(b"\r\n ")?;
+            start = i;
+            //the leading space counts towards the limit
+            limit = 74;
+        }
+    }
+    writer.write_all(&line.as_bytes()[start..])?;
+    writer.write_all(b"\r\n")?;
+    Ok(())
+}
+
+/// Same lesson on the same day always gets the same UID so re-imports update the event
+fn event_uid(lesson: &Lesson, host: &str, primus_id: i32) -> String {
+    format!(
+        "{}-{}-{}@{}",
+        primus_id,
+        lesson.reservation_id,
+        lesson.date.format("%Y%m%d"),
+        host
+    )
+}
+
+fn write_ics(lessons: &[Lesson], wilma: &Wilma, mut writer: impl Write) -> Result<()> {
+    let role = wilma
+        .role
+        .as_ref()
+        .ok_or_else(|| anyhow!("No role selected"))?;
+    let host = wilma
+        .base_url
+        .host_str()
+        .ok_or_else(|| anyhow!("Wilma url has no host"))?;
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+    write_line(&mut writer, "BEGIN:VCALENDAR")?;
+    write_line(&mut writer, "VERSION:2.0")?;
+    write_line(
+        &mut writer,
+        &format!(
+            "PRODID:-//{}//{}//EN",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        ),
+    )?;
+    write_line(&mut writer, "CALSCALE:GREGORIAN")?;
+    write_line(
+        &mut writer,
+        &format!(
+            "X-WR-CALNAME:{}",
+            escape_text(&format!("{} ({})", role.name, wilma.name))
+        ),
+    )?;
+    write_line(&mut writer, &format!("X-WR-TIMEZONE:{TIMEZONE}"))?;
+    for line in VTIMEZONE {
+        write_line(&mut writer, line)?;
+    }
+
+    for lesson in lessons {
+        write_line(&mut writer, "BEGIN:VEVENT")?;
+        write_line(
+            &mut writer,
+            &format!("UID:{}", event_uid(lesson, host, role.primus_id)),
+        )?;
+        write_line(&mut writer, &format!("DTSTAMP:{stamp}"))?;
+        write_line(
+            &mut writer,
+            &format!(
+                "DTSTART;TZID={TIMEZONE}:{}",
+                lesson.date.and_time(lesson.start).format("%Y%m%dT%H%M%S")
+            ),
+        )?;
+        write_line(
+            &mut writer,
+            &format!(
+                "DTEND;TZID={TIMEZONE}:{}",
+                lesson.date.and_time(lesson.end).format("%Y%m%dT%H%M%S")
+            ),
+        )?;
+        write_line(
+            &mut writer,
+            &format!("SUMMARY:{}", escape_text(&lesson.subject)),
+        )?;
+        if !lesson.rooms.is_empty() {
+            write_line(
+                &mut writer,
+                &format!("LOCATION:{}", escape_text(&lesson.rooms.join(", "))),
+            )?;
+        }
+        let description = [("Groups", &lesson.groups), ("Teachers", &lesson.teachers)]
+            .iter()
+            .filter(|(_, values)| !values.is_empty())
+            .map(|(label, values)| format!("{label}: {}", values.join(", ")))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if !description.is_empty() {
+            write_line(
+                &mut writer,
+                &format!("DESCRIPTION:{}", escape_text(&description)),
+            )?;
+        }
+        write_line(&mut writer, "END:VEVENT")?;
+    }
+
+    write_line(&mut writer, "END:VCALENDAR")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn dump_to_writer(
+    lessons: &[Lesson],
+    wilma: &Wilma,
+    writer: impl Write,
+    format: Format,
+) -> Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer(writer, lessons)?;
+        }
+        Format::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            for l in lessons {
+                csv.serialize(LessonRow::from(l))?;
+            }
+            csv.flush()?;
+        }
+        Format::Ics => {
+            write_ics(lessons, wilma, writer)?;
+        }
+    };
+
+    Ok(())
+}
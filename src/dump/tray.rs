@@ -0,0 +1,32 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use std::io::Write;
+
+use crate::wilma::models::CourseOffering;
+
+#[derive(Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+pub fn dump_to_writer(
+    offerings: &[CourseOffering],
+    writer: impl Write,
+    format: Format,
+) -> Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer(writer, offerings)?;
+        }
+        Format::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            for o in offerings {
+                csv.serialize(o)?;
+            }
+            csv.flush()?;
+        }
+    };
+
+    Ok(())
+}
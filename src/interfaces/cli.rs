@@ -1,101 +1,423 @@
+use std::future::Future;
+use std::path::Path;
+use std::time::Duration;
+
 use crate::dump;
-use crate::ipc::{self, IPCMessage};
-use crate::wilma::{self, api::models::OpenIDProvider, Wilma, WilmaApi};
+use crate::reg;
+use crate::session;
+use crate::wilma::{
+    self,
+    api::models::{MessageFolder, NewsScope, OpenIDProvider, WilmaRole, WilmaRoleType},
+    api::schedule::week_of,
+    auth::RedirectMode,
+    download::{self, Downloader},
+    ReloginCallback, SessionExpired, Wilma, WilmaApi,
+};
 
 use super::{Interface, InterfaceContext};
 
 use clap::error::ErrorKind;
-use clap::{CommandFactory, Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use dialoguer::theme::ColorfulTheme;
 use tokio::runtime::Handle;
+use tokio::sync::broadcast;
 
-use reqwest::Url;
+use reqwest::{Client, Url};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, ensure, Result};
+use chrono::{Local, NaiveDate};
+use log::*;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct Cli {
     #[arg(short, long, value_parser = Url::parse)]
     wilma: Option<Url>,
 
+    /// Log in with a Wilma username and password instead of OpenID
+    #[arg(short, long)]
+    username: Option<String>,
+
+    /// Receive the OpenID redirect on a local http listener instead of the wilma:// handler
+    #[arg(long)]
+    loopback: bool,
+
+    /// Print the OpenID login url and read the final wilma:// url from the terminal
+    #[arg(long, conflicts_with = "loopback")]
+    headless: bool,
+
+    /// Seconds to wait for the OpenID login to finish in the browser
+    #[arg(long, default_value_t = wilma::auth::DEFAULT_LOGIN_TIMEOUT.as_secs())]
+    login_timeout: u64,
+
+    /// Reuse the saved session if it is still valid and save new sessions for later runs
+    #[arg(short, long)]
+    session: bool,
+
+    /// Run the command once for every role of the account, output files and directories get the
+    /// role slug added to their name. Failing roles are reported at the end
+    #[arg(long)]
+    all_roles: bool,
+
+    /// How many attachments to download at the same time
+    #[arg(long, default_value_t = wilma::download::DEFAULT_DOWNLOAD_LIMIT)]
+    download_limit: usize,
+
+    /// Use the command line interface
+    #[arg(long, conflicts_with = "gui")]
+    cli: bool,
+
+    /// Use the graphical interface, even when started from a terminal
+    #[arg(long)]
+    gui: bool,
+
+    // optional so a bare --gui parses, the CLI requires it
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
-#[derive(Subcommand, Debug)]
+/// `Some(true)` for `--gui` and `Some(false)` for `--cli`. `None` if neither was given or the
+/// arguments don't parse, the CLI reports parse errors if it ends up being used
+pub fn requested_gui() -> Option<bool> {
+    let cli = Cli::try_parse().ok()?;
+    match (cli.gui, cli.cli) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
 enum Commands {
+    #[command(flatten)]
+    Data(DataCommand),
+    #[command(flatten)]
+    Handler(HandlerCommand),
+}
+
+/// Commands that dump or show data and need a logged in wilma
+#[derive(Subcommand, Debug, Clone)]
+enum DataCommand {
     Courses {
         #[command(subcommand)]
         subcommand: CourseOption,
     },
+    Schedule {
+        #[command(subcommand)]
+        subcommand: ScheduleOption,
+    },
+    Messages {
+        #[command(subcommand)]
+        subcommand: MessageOption,
+    },
+    Exams {
+        #[command(subcommand)]
+        subcommand: ExamOption,
+    },
+    Attendance {
+        #[command(subcommand)]
+        subcommand: AttendanceOption,
+    },
+    Homework {
+        #[command(subcommand)]
+        subcommand: HomeworkOption,
+    },
+    News {
+        #[command(subcommand)]
+        subcommand: NewsOption,
+    },
+    /// Every group on offer in the course trays
+    Tray {
+        #[command(subcommand)]
+        subcommand: TrayOption,
+    },
+    /// Teaching groups and their students, for teacher roles
+    Groups {
+        #[command(subcommand)]
+        subcommand: GroupOption,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum HandlerCommand {
+    /// Register the wilma:// protocol handler permanently for this executable
+    InstallHandler,
+    /// Remove the wilma:// protocol handler
+    UninstallHandler,
+    /// Show where the wilma:// protocol handler points to
+    HandlerStatus,
 }
 
-#[derive(Subcommand, Debug)]
+#[derive(Subcommand, Debug, Clone)]
 enum CourseOption {
     StudyPoints,
     Dump {
         file: Option<String>,
+        #[arg(long, value_enum, ignore_case = true, default_value_t = dump::courses::Format::Json)]
+        format: dump::courses::Format,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum ScheduleOption {
+    Dump {
+        file: Option<String>,
+        #[arg(long, value_enum, ignore_case = true, default_value_t = dump::schedule::Format::Json)]
+        format: dump::schedule::Format,
+        /// First day to dump (YYYY-MM-DD), defaults to monday of the current week
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Last day to dump (YYYY-MM-DD), defaults to sunday of the week of --from
+        #[arg(long)]
+        to: Option<NaiveDate>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum MessageOption {
+    /// List the messages without opening them
+    List {
+        /// Can be repeated, defaults to all of them
+        #[arg(long, value_enum, ignore_case = true)]
+        folder: Vec<MessageFolder>,
+    },
+    /// Open every message and dump them with their replies
+    Dump {
+        path: Option<String>,
+        #[arg(long, value_enum, ignore_case = true, default_value_t = dump::messages::Format::Json)]
+        format: dump::messages::Format,
+        /// Can be repeated, defaults to all of them
+        #[arg(long, value_enum, ignore_case = true)]
+        folder: Vec<MessageFolder>,
+        /// Also download the attachments into this directory
         #[arg(long)]
-        format: Option<String>,
+        attachments: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum ExamOption {
+    Dump {
+        file: Option<String>,
+        #[arg(long, value_enum, ignore_case = true, default_value_t = dump::exams::Format::Json)]
+        format: dump::exams::Format,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum AttendanceOption {
+    /// Show mark counts per course and per period
+    Stats,
+    Dump {
+        file: Option<String>,
+        #[arg(long, value_enum, ignore_case = true, default_value_t = dump::attendance::Format::Json)]
+        format: dump::attendance::Format,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum HomeworkOption {
+    Dump {
+        file: Option<String>,
+        #[arg(long, value_enum, ignore_case = true, default_value_t = dump::homework::Format::Json)]
+        format: dump::homework::Format,
     },
 }
 
+#[derive(Subcommand, Debug, Clone)]
+enum NewsOption {
+    /// List the school-wide and personal news without fetching them
+    List,
+    /// Fetch every news item and dump them
+    Dump {
+        path: Option<String>,
+        /// md writes a directory with one file per item and the attachments
+        #[arg(long, value_enum, ignore_case = true, default_value_t = dump::news::Format::Json)]
+        format: dump::news::Format,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum GroupOption {
+    Dump {
+        file: Option<String>,
+        #[arg(long, value_enum, ignore_case = true, default_value_t = dump::groups::Format::Csv)]
+        format: dump::groups::Format,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum TrayOption {
+    Dump {
+        file: Option<String>,
+        #[arg(long, value_enum, ignore_case = true, default_value_t = dump::tray::Format::Json)]
+        format: dump::tray::Format,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum OpenIDMode {
+    Redirect(RedirectMode),
+    Headless,
+}
+
+/// Roles that have their own data, the account level passwd role doesn't
+fn has_data(role: &WilmaRole) -> bool {
+    !matches!(role.type_, WilmaRoleType::Passwd)
+}
+
+/// Adds the role slug before the extension, "courses.json" becomes "courses-<slug>.json"
+fn with_slug(path: &str, slug: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{slug}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{slug}"),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// The given path or the one asked from the user, when running for every role the default is
+/// used without asking and the slug is added
+fn dump_path(path: Option<String>, default: String, slug: Option<&str>) -> Result<String> {
+    Ok(match (path, slug) {
+        (path, Some(slug)) => with_slug(&path.unwrap_or(default), slug),
+        (Some(path), None) => path,
+        (None, None) => dialoguer::Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Path to dump file")
+            .default(default)
+            .interact_text()?,
+    })
+}
+
+fn print_attendance(summaries: &[dump::attendance::AttendanceSummary]) {
+    let width = summaries
+        .iter()
+        .map(|s| s.name.chars().count())
+        .chain([4])
+        .max()
+        .unwrap_or_default();
+
+    println!(
+        "{:width$}  {:>7}  {:>9}  {:>4}  {:>5}  {:>5}",
+        "Name", "Excused", "Unexcused", "Late", "Other", "Total"
+    );
+    for s in summaries {
+        println!(
+            "{:width$}  {:>7}  {:>9}  {:>4}  {:>5}  {:>5}",
+            s.name,
+            s.excused,
+            s.unexcused,
+            s.late,
+            s.other,
+            s.total()
+        );
+    }
+}
+
+/// The folders given with `--folder`, every folder when there are none
+fn folders_or_all(folders: Vec<MessageFolder>) -> Vec<MessageFolder> {
+    match folders.is_empty() {
+        true => MessageFolder::value_variants().to_vec(),
+        false => folders,
+    }
+}
+
+/// How long to wait for the browser and what cancels the wait
+#[derive(Clone)]
+struct LoginWait {
+    timeout: Duration,
+    cancel: broadcast::Sender<()>,
+}
+
+impl LoginWait {
+    async fn run<T>(&self, login: impl Future<Output = Result<T>>) -> Result<T> {
+        let mut cancel = self.cancel.subscribe();
+        wilma::auth::abortable(login, self.timeout, async move {
+            let _ = cancel.recv().await;
+        })
+        .await
+    }
+}
+
+#[derive(Clone)]
 pub struct CliInterface {
     rt: Handle,
+    cancel_login: broadcast::Sender<()>,
 }
 
 impl Interface for CliInterface {
     fn new(handle: Handle) -> Self {
-        Self { rt: handle }
+        Self {
+            rt: handle,
+            cancel_login: broadcast::channel(1).0,
+        }
     }
 
     fn start(self, ctx: InterfaceContext) -> Result<()> {
+        // Ctrl-C cancels a pending login, otherwise exits like it would without a handler
+        let cancel_login = self.cancel_login.clone();
+        self.rt.spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                if cancel_login.send(()).is_err() {
+                    reg::release_handler();
+                    std::process::exit(130);
+                }
+            }
+        });
+
         //Just block the thread, no gui here
         self.rt.block_on(async {
             let cli = Cli::parse();
 
-            let mut wilma = self.get_wilma(&ctx, &cli).await?;
-            self.login(&ctx, &mut wilma).await?;
-            self.set_role(&ctx, &mut wilma).await?;
+            let command = match cli.command.clone() {
+                Some(Commands::Data(command)) => command,
+                Some(Commands::Handler(command)) => return self.run_handler_command(command),
+                None => Cli::command()
+                    .error(ErrorKind::MissingSubcommand, "A subcommand is required")
+                    .exit(),
+            };
 
-            match cli.command {
-                Commands::Courses { subcommand } => {
-                    let courses = wilma.get_courses(&ctx.client).await?;
-                    match subcommand {
-                        CourseOption::StudyPoints => {
-                            let (selected, earned) =
-                                dump::courses::calculate_study_points(&courses);
+            let wilma = match self.restore_session(&ctx, &cli).await? {
+                Some(wilma) => wilma,
+                None => {
+                    let mut wilma = self.get_wilma(&ctx, &cli).await?;
+                    self.login(&ctx, &mut wilma, &cli).await?;
+                    self.set_role(&ctx, &mut wilma, cli.all_roles).await?;
 
-                            println!("Current credits: {earned}");
-                            println!("Credits from selected courses: {selected}");
-                        }
-                        CourseOption::Dump { file, format } => {
-                            let format = format
-                                .unwrap_or_else(|| String::from("json"))
-                                .to_lowercase();
-                            let dump_format = match format.as_str() {
-                                "json" => dump::courses::Format::Json,
-                                "csv" => dump::courses::Format::Csv,
-                                _ => {
-                                    return Err(anyhow!("Invalid format: {}", format));
-                                }
-                            };
-
-                            let path = match file {
-                                Some(path) => path,
-                                None => dialoguer::Input::with_theme(&ColorfulTheme::default())
-                                    .with_prompt("Path to dump file")
-                                    .default(format!("courses.{}", format))
-                                    .interact_text()?,
-                            };
+                    if cli.session {
+                        session::save(&wilma)?;
+                    }
 
-                            let file = std::fs::File::create(path)?;
-                            dump::courses::dump_to_writer(&courses, file, dump_format)?;
-                        }
+                    wilma
+                }
+            };
+
+            if cli.all_roles {
+                //one role failing, e.g. a teacher role without courses, shouldn't stop the rest
+                let roles = wilma.get_roles(&ctx.client).await?;
+                let mut failed = vec![];
+                for role in roles.iter().filter(|r| has_data(r)) {
+                    let mut wilma = wilma.clone();
+                    wilma.set_role(role)?;
+                    info!("Running for {} ({})", role.name, role.slug);
+                    if let Err(e) = self
+                        .run_command(&ctx, &cli, command.clone(), &wilma, Some(&role.slug))
+                        .await
+                    {
+                        error!("Failed for {} ({}): {e:#}", role.name, role.slug);
+                        failed.push(role.slug.as_str());
                     }
                 }
+                ensure!(
+                    failed.is_empty(),
+                    "Failed for {} of the roles: {}",
+                    failed.len(),
+                    failed.join(", ")
+                );
+            } else {
+                self.run_command(&ctx, &cli, command, &wilma, None).await?;
             }
 
             Ok(())
@@ -104,6 +426,315 @@ impl Interface for CliInterface {
 }
 
 impl CliInterface {
+    /// Runs a data command for the role set on `wilma`, `slug` is given when running for
+    /// every role so outputs don't overwrite each other
+    async fn run_command(
+        &self,
+        ctx: &InterfaceContext,
+        cli: &Cli,
+        command: DataCommand,
+        wilma: &Wilma,
+        slug: Option<&str>,
+    ) -> Result<()> {
+        match command {
+            DataCommand::Courses { subcommand } => {
+                let courses = wilma.get_courses(&ctx.client).await?;
+                match subcommand {
+                    CourseOption::StudyPoints => {
+                        let (selected, earned) = dump::courses::calculate_study_points(&courses);
+
+                        println!("Current credits: {earned}");
+                        println!("Credits from selected courses: {selected}");
+                    }
+                    CourseOption::Dump { file, format } => {
+                        let path = dump_path(
+                            file,
+                            format!("courses.{}", dump::format_name(&format)),
+                            slug,
+                        )?;
+
+                        let file = std::fs::File::create(path)?;
+                        dump::courses::dump_to_writer(&courses, file, format)?;
+                    }
+                }
+            }
+            DataCommand::Schedule { subcommand } => match subcommand {
+                ScheduleOption::Dump {
+                    file,
+                    format,
+                    from,
+                    to,
+                } => {
+                    let from = from.unwrap_or_else(|| *week_of(Local::now().date_naive()).start());
+                    let to = to.unwrap_or_else(|| *week_of(from).end());
+                    ensure!(from <= to, "--from must not be after --to");
+
+                    let lessons = wilma.get_schedule(&ctx.client, from..=to).await?;
+
+                    let path = dump_path(
+                        file,
+                        format!("schedule.{}", dump::format_name(&format)),
+                        slug,
+                    )?;
+
+                    let file = std::fs::File::create(path)?;
+                    dump::schedule::dump_to_writer(&lessons, wilma, file, format)?;
+                }
+            },
+            DataCommand::Messages { subcommand } => match subcommand {
+                MessageOption::List { folder } => {
+                    for folder in folders_or_all(folder) {
+                        for m in wilma.get_messages(&ctx.client, folder).await? {
+                            println!(
+                                "{:?}\t{}\t{}\t{}\t{}",
+                                m.folder, m.id, m.timestamp, m.sender, m.subject
+                            );
+                        }
+                    }
+                }
+                MessageOption::Dump {
+                    path,
+                    format,
+                    folder,
+                    attachments,
+                } => {
+                    let folders = folders_or_all(folder);
+
+                    let path = dump_path(
+                        path,
+                        match format {
+                            dump::messages::Format::Maildir => String::from("messages"),
+                            _ => format!("messages.{}", dump::format_name(&format)),
+                        },
+                        slug,
+                    )?;
+
+                    let messages =
+                        wilma::api::messages::get_full_messages(&ctx.client, wilma, &folders)
+                            .await?;
+                    info!("Fetched {} messages", messages.len());
+
+                    match format {
+                        dump::messages::Format::Maildir => {
+                            dump::messages::dump_to_maildir(&messages, wilma, Path::new(&path))?
+                        }
+                        _ => {
+                            let file = std::fs::File::create(path)?;
+                            dump::messages::dump_to_writer(&messages, wilma, file, format)?;
+                        }
+                    }
+
+                    if let Some(dir) = attachments {
+                        let dir = match slug {
+                            Some(slug) => with_slug(&dir, slug),
+                            None => dir,
+                        };
+                        let downloaded = Downloader::new(dir, cli.download_limit)
+                            .download(
+                                &ctx.client,
+                                wilma,
+                                download::message_attachments(&messages, &wilma.get_url()?),
+                            )
+                            .await?;
+                        info!("{} attachments in the archive", downloaded.len());
+                    }
+                }
+            },
+            DataCommand::Exams { subcommand } => match subcommand {
+                ExamOption::Dump { file, format } => {
+                    let exams = wilma.get_exams(&ctx.client).await?;
+
+                    let path =
+                        dump_path(file, format!("exams.{}", dump::format_name(&format)), slug)?;
+
+                    let file = std::fs::File::create(path)?;
+                    dump::exams::dump_to_writer(&exams, file, format)?;
+                }
+            },
+            DataCommand::Attendance { subcommand } => {
+                let marks = wilma.get_attendance(&ctx.client).await?;
+                match subcommand {
+                    AttendanceOption::Stats => {
+                        let today = Local::now().date_naive();
+                        let first = marks.iter().map(|m| m.date).min().unwrap_or(today);
+                        let last = marks.iter().map(|m| m.date).max().unwrap_or(today);
+                        let periods = wilma.get_periods(&ctx.client, first..=last).await?;
+
+                        println!("Per course");
+                        print_attendance(&dump::attendance::summarize_by_course(&marks));
+                        println!();
+                        println!("Per period");
+                        print_attendance(&dump::attendance::summarize_by_period(&marks, &periods));
+                    }
+                    AttendanceOption::Dump { file, format } => {
+                        let path = dump_path(
+                            file,
+                            format!("attendance.{}", dump::format_name(&format)),
+                            slug,
+                        )?;
+
+                        let file = std::fs::File::create(path)?;
+                        dump::attendance::dump_to_writer(&marks, file, format)?;
+                    }
+                }
+            }
+            DataCommand::Homework { subcommand } => match subcommand {
+                HomeworkOption::Dump { file, format } => {
+                    let entries = wilma.get_homework(&ctx.client).await?;
+
+                    let path = dump_path(
+                        file,
+                        format!("homework.{}", dump::format_name(&format)),
+                        slug,
+                    )?;
+
+                    let file = std::fs::File::create(path)?;
+                    dump::homework::dump_to_writer(&entries, file, format)?;
+                }
+            },
+            DataCommand::News { subcommand } => match subcommand {
+                NewsOption::List => {
+                    for scope in [NewsScope::School, NewsScope::Role] {
+                        for item in wilma.get_news_list(&ctx.client, scope).await? {
+                            println!(
+                                "{:?}\t{}\t{}\t{}",
+                                item.scope,
+                                item.id,
+                                item.published.map_or_else(String::new, |d| d.to_string()),
+                                item.title
+                            );
+                        }
+                    }
+                }
+                NewsOption::Dump { path, format } => {
+                    let path = dump_path(
+                        path,
+                        match format {
+                            dump::news::Format::Markdown => String::from("news"),
+                            _ => format!("news.{}", dump::format_name(&format)),
+                        },
+                        slug,
+                    )?;
+
+                    let items = wilma::api::news::get_full_news(&ctx.client, wilma).await?;
+                    info!("Fetched {} news items", items.len());
+
+                    match format {
+                        dump::news::Format::Markdown => {
+                            dump::news::dump_to_directory(
+                                &items,
+                                &ctx.client,
+                                wilma,
+                                Path::new(&path),
+                                cli.download_limit,
+                            )
+                            .await?
+                        }
+                        _ => {
+                            let file = std::fs::File::create(path)?;
+                            dump::news::dump_to_writer(&items, file, format)?;
+                        }
+                    }
+                }
+            },
+            DataCommand::Tray { subcommand } => match subcommand {
+                TrayOption::Dump { file, format } => {
+                    let offerings = wilma.get_course_tray(&ctx.client).await?;
+
+                    let path =
+                        dump_path(file, format!("tray.{}", dump::format_name(&format)), slug)?;
+
+                    let file = std::fs::File::create(path)?;
+                    dump::tray::dump_to_writer(&offerings, file, format)?;
+                }
+            },
+            DataCommand::Groups { subcommand } => match subcommand {
+                GroupOption::Dump { file, format } => {
+                    let groups = wilma.get_groups(&ctx.client).await?;
+
+                    let path =
+                        dump_path(file, format!("groups.{}", dump::format_name(&format)), slug)?;
+
+                    let file = std::fs::File::create(path)?;
+                    dump::groups::dump_to_writer(&groups, file, format)?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Protocol handler commands don't need a wilma
+    fn run_handler_command(&self, command: HandlerCommand) -> Result<()> {
+        match command {
+            HandlerCommand::InstallHandler => {
+                reg::register_wilma_handler(true)?;
+                println!("Protocol handler installed");
+            }
+            HandlerCommand::UninstallHandler => {
+                reg::unregister_wilma_handler()?;
+                println!("Protocol handler uninstalled");
+            }
+            HandlerCommand::HandlerStatus => match reg::get_registration()? {
+                Some(registration) => {
+                    println!("Protocol handler registered");
+                    println!("Command: {}", registration.command);
+                    println!("Installed: {}", registration.persistent);
+                    println!("Runs this executable: {}", registration.current);
+                }
+                None => println!("Protocol handler not registered"),
+            },
+        }
+
+        Ok(())
+    }
+
+    async fn restore_session(&self, ctx: &InterfaceContext, cli: &Cli) -> Result<Option<Wilma>> {
+        if !cli.session {
+            return Ok(None);
+        }
+
+        let wilma = match session::load() {
+            Ok(Some(wilma)) => wilma,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                warn!("Could not read saved session, logging in again: {e:#}");
+                return Ok(None);
+            }
+        };
+
+        if let Some(url) = &cli.wilma {
+            if *url != wilma.base_url {
+                debug!("Saved session is for a different wilma");
+                return Ok(None);
+            }
+        }
+
+        let role = wilma.role.as_ref().unwrap();
+        match wilma.get_roles(&ctx.client).await {
+            Ok(roles) if roles.iter().any(|r| r.primus_id == role.primus_id) => {
+                info!("Using saved session for {}", wilma.base_url);
+                let mut wilma = wilma;
+                wilma.set_relogin(saving_session(self.session_relogin(ctx, cli)));
+                Ok(Some(wilma))
+            }
+            Ok(_) => {
+                info!("Saved session role is no longer available, logging in again");
+                session::clear()?;
+                Ok(None)
+            }
+            Err(e) if e.is::<SessionExpired>() => {
+                info!("Saved session has expired, logging in again");
+                session::clear()?;
+                Ok(None)
+            }
+            Err(e) => {
+                warn!("Could not check saved session, logging in again: {e:#}");
+                Ok(None)
+            }
+        }
+    }
+
     async fn get_wilma(&self, ctx: &InterfaceContext, cli: &Cli) -> Result<Wilma> {
         let wilma = match &cli.wilma {
             Some(url) => {
@@ -139,9 +770,24 @@ impl CliInterface {
         Ok(wilma)
     }
 
-    async fn set_role(&self, ctx: &InterfaceContext, wilma: &mut Wilma) -> Result<()> {
+    /// Asks for the role, with `all_roles` the first one is used and only matters for the
+    /// saved session
+    async fn set_role(
+        &self,
+        ctx: &InterfaceContext,
+        wilma: &mut Wilma,
+        all_roles: bool,
+    ) -> Result<()> {
         let roles = wilma.get_roles(&ctx.client).await?;
 
+        if all_roles {
+            let role = roles
+                .iter()
+                .find(|r| has_data(r))
+                .ok_or_else(|| anyhow!("The account has no roles"))?;
+            return wilma.set_role(role);
+        }
+
         let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
             .with_prompt("Select role:")
             .items(
@@ -162,47 +808,182 @@ impl CliInterface {
         &self,
         _ctx: &InterfaceContext,
         providers: Vec<OpenIDProvider>,
-    ) -> Result<OpenIDProvider> {
+    ) -> Result<Option<OpenIDProvider>> {
+        let mut items = providers
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<&str>>();
+        items.push("Wilma username and password");
+
         let selection = dialoguer::FuzzySelect::with_theme(&ColorfulTheme::default())
-            .with_prompt("Select OpenID provider")
-            .items(
-                &providers
-                    .iter()
-                    .map(|p| p.name.as_str())
-                    .collect::<Vec<&str>>(),
-            )
+            .with_prompt("Select login method")
+            .items(&items)
             .default(0)
             .interact()?;
-        Ok(providers[selection].clone())
-    }
-
-    async fn login(&self, ctx: &InterfaceContext, wilma: &mut Wilma) -> Result<()> {
-        match wilma.get_providers(&ctx.client).await? {
-            Some(providers) => {
-                let provider = self.get_provider(ctx, providers)?;
-
-                wilma::auth::oauth_authorize(&ctx.client, &provider).await?;
-                match ipc::receive_data().await? {
-                    IPCMessage::TokenResponse {
-                        access_token,
-                        id_token,
-                    } => {
-                        wilma
-                            .openid_login(
-                                &ctx.client,
-                                provider.configuration.clone(),
-                                provider.client_id.clone(),
-                                access_token,
-                                id_token,
-                            )
-                            .await?
+        Ok(providers.get(selection).cloned())
+    }
+
+    /// Logs in and sets up logging in the same way again when the session expires
+    async fn login(&self, ctx: &InterfaceContext, wilma: &mut Wilma, cli: &Cli) -> Result<()> {
+        let relogin = match &cli.username {
+            Some(username) => {
+                self.password_login(ctx, wilma, Some(username.clone()))
+                    .await?
+            }
+            None => {
+                let provider = match wilma.get_providers(&ctx.client).await? {
+                    Some(providers) if !providers.is_empty() => {
+                        self.get_provider(ctx, providers)?
                     }
-                    _ => unreachable!(),
+                    _ => None,
                 };
 
-                Ok(())
+                match provider {
+                    Some(provider) => self.openid_login(ctx, wilma, provider, cli).await?,
+                    None => self.password_login(ctx, wilma, None).await?,
+                }
             }
-            None => Err(anyhow!("Selected wilma does not support OpenID")),
-        }
+        };
+
+        wilma.set_relogin(match cli.session {
+            true => saving_session(relogin),
+            false => relogin,
+        });
+
+        Ok(())
     }
+
+    /// A saved session doesn't know how it was made, so it logs in again the way a fresh run
+    /// would
+    fn session_relogin(&self, ctx: &InterfaceContext, cli: &Cli) -> ReloginCallback {
+        let (interface, client, cli) = (self.clone(), ctx.client.clone(), cli.clone());
+        ReloginCallback::new(move |mut wilma| {
+            let (interface, client, cli) = (interface.clone(), client.clone(), cli.clone());
+            Box::pin(async move {
+                interface
+                    .login(&InterfaceContext::new(client), &mut wilma, &cli)
+                    .await?;
+                Ok(wilma)
+            })
+        })
+    }
+
+    async fn openid_login(
+        &self,
+        ctx: &InterfaceContext,
+        wilma: &mut Wilma,
+        provider: OpenIDProvider,
+        cli: &Cli,
+    ) -> Result<ReloginCallback> {
+        let mode = if cli.headless {
+            OpenIDMode::Headless
+        } else if cli.loopback {
+            OpenIDMode::Redirect(RedirectMode::Loopback)
+        } else {
+            OpenIDMode::Redirect(RedirectMode::ProtocolHandler)
+        };
+
+        let wait = LoginWait {
+            timeout: Duration::from_secs(cli.login_timeout),
+            cancel: self.cancel_login.clone(),
+        };
+
+        openid_authenticate(&ctx.client, wilma, &provider, mode, &wait).await?;
+
+        let client = ctx.client.clone();
+        Ok(ReloginCallback::new(move |mut wilma| {
+            let client = client.clone();
+            let provider = provider.clone();
+            let wait = wait.clone();
+            Box::pin(async move {
+                openid_authenticate(&client, &mut wilma, &provider, mode, &wait).await?;
+                Ok(wilma)
+            })
+        }))
+    }
+
+    async fn password_login(
+        &self,
+        ctx: &InterfaceContext,
+        wilma: &mut Wilma,
+        username: Option<String>,
+    ) -> Result<ReloginCallback> {
+        let username = match username {
+            Some(username) => username,
+            None => dialoguer::Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Username")
+                .interact_text()?,
+        };
+        let password = dialoguer::Password::with_theme(&ColorfulTheme::default())
+            .with_prompt("Password")
+            .interact()?;
+
+        wilma
+            .password_login(&ctx.client, username.clone(), password.clone())
+            .await?;
+
+        let client = ctx.client.clone();
+        Ok(ReloginCallback::new(move |mut wilma| {
+            let client = client.clone();
+            let username = username.clone();
+            let password = password.clone();
+            Box::pin(async move {
+                wilma.password_login(&client, username, password).await?;
+                Ok(wilma)
+            })
+        }))
+    }
+}
+
+/// Saves the session again after every re-login, so the next run can reuse it
+fn saving_session(relogin: ReloginCallback) -> ReloginCallback {
+    ReloginCallback::new(move |wilma| {
+        let relogin = relogin.clone();
+        Box::pin(async move {
+            let wilma = relogin.call(wilma).await?;
+            if let Err(e) = session::save(&wilma) {
+                warn!("Could not save the renewed session: {e:#}");
+            }
+            Ok(wilma)
+        })
+    })
+}
+
+async fn openid_authenticate(
+    client: &Client,
+    wilma: &mut Wilma,
+    provider: &OpenIDProvider,
+    mode: OpenIDMode,
+    wait: &LoginWait,
+) -> Result<()> {
+    let token_data = match mode {
+        OpenIDMode::Redirect(mode) => {
+            wait.run(wilma::auth::oauth_authorize(client, provider, mode))
+                .await?
+        }
+        // the prompt blocks, so it can't be cancelled or timed out here
+        OpenIDMode::Headless => {
+            wilma::auth::oauth_authorize_manual(client, provider, read_redirect_url).await?
+        }
+    };
+    wilma
+        .openid_login(
+            client,
+            provider.configuration.clone(),
+            provider.client_id.clone(),
+            token_data.access_token,
+            token_data.id_token,
+        )
+        .await
+}
+
+fn read_redirect_url(auth_url: &Url) -> Result<Url> {
+    println!("Open this url in a browser and log in:\n\n{auth_url}\n");
+    println!("The browser will fail to open the final wilma://oauth url, copy it from the address bar or developer tools.");
+
+    let url: String = dialoguer::Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Redirect url")
+        .interact_text()?;
+
+    Ok(Url::parse(url.trim())?)
 }
@@ -1,17 +1,26 @@
 use std::{
     fmt::Display,
+    path::Path,
     sync::mpsc::{channel, Receiver, Sender},
 };
 
+use chrono::{Local, NaiveDate};
+use reqwest::Client;
 use tokio::runtime::Handle;
+use tokio::sync::oneshot;
 
 use crate::{
-    dump,
-    ipc::{self, IPCMessage},
+    dump, reg,
     wilma::{
         self,
-        models::{Course, OpenIDProvider, WilmaRole},
-        Wilma, WilmaApi,
+        api::schedule::week_of,
+        auth::RedirectMode,
+        download::{self, Downloader, DEFAULT_DOWNLOAD_LIMIT},
+        models::{
+            AttendanceMark, Course, CourseOffering, Exam, HomeworkEntry, Lesson, Message,
+            MessageFolder, NewsItem, OpenIDProvider, TeachingGroup, WilmaRole, WilmaRoleType,
+        },
+        ReloginCallback, Wilma, WilmaApi,
     },
 };
 
@@ -43,12 +52,52 @@ impl Interface for GuiInterface {
 #[derive(PartialEq)]
 enum Dumper {
     Courses,
+    Schedule,
+    Messages,
+    Exams,
+    Attendance,
+    Homework,
+    News,
+    Groups,
+    Tray,
+}
+
+impl Dumper {
+    const ALL: [Dumper; 9] = [
+        Dumper::Courses,
+        Dumper::Schedule,
+        Dumper::Messages,
+        Dumper::Exams,
+        Dumper::Attendance,
+        Dumper::Homework,
+        Dumper::News,
+        Dumper::Groups,
+        Dumper::Tray,
+    ];
+
+    /// Course choices are only for students and guardians, teaching groups only for teachers
+    fn supports(&self, role: Option<&WilmaRole>) -> bool {
+        let teacher = matches!(role.map(|r| &r.type_), Some(WilmaRoleType::Teacher));
+        match self {
+            Dumper::Courses => !teacher,
+            Dumper::Groups => teacher,
+            _ => true,
+        }
+    }
 }
 
 impl Display for Dumper {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Dumper::Courses => write!(f, "Courses"),
+            Dumper::Schedule => write!(f, "Schedule"),
+            Dumper::Messages => write!(f, "Messages"),
+            Dumper::Exams => write!(f, "Exams"),
+            Dumper::Attendance => write!(f, "Attendance"),
+            Dumper::Homework => write!(f, "Homework"),
+            Dumper::News => write!(f, "News"),
+            Dumper::Groups => write!(f, "Groups"),
+            Dumper::Tray => write!(f, "Course tray"),
         }
     }
 }
@@ -58,8 +107,22 @@ enum AppMessage {
     WilmaList(Vec<Wilma>),
     WilmaProviderList(Option<Vec<OpenIDProvider>>),
     WilmaLogin(Box<Wilma>),
+    WilmaLoginFailed(String),
     WilmaRoles(Vec<WilmaRole>),
     WilmaCourses(Vec<Course>),
+    WilmaSchedule(Vec<Lesson>),
+    WilmaMessages(Vec<Message>),
+    WilmaAttachments(usize),
+    WilmaAttachmentsFailed(String),
+    WilmaExams(Vec<Exam>),
+    WilmaAttendance(Vec<AttendanceMark>),
+    WilmaHomework(Vec<HomeworkEntry>),
+    WilmaNews(Vec<NewsItem>),
+    WilmaNewsDumped,
+    WilmaNewsDumpFailed(String),
+    WilmaGroups(Vec<TeachingGroup>),
+    WilmaTray(Vec<CourseOffering>),
+    WilmaFetchFailed(String),
 }
 
 struct GuiApp {
@@ -73,18 +136,63 @@ struct GuiApp {
     wilma_roles: Option<Vec<WilmaRole>>,
     selected_wilma: Option<Wilma>,
     logging_in: bool,
+    loopback_redirect: bool,
+    cancel_login: Option<oneshot::Sender<()>>,
+    login_username: String,
+    login_password: String,
+    login_error: Option<String>,
 
     dumper: Option<Dumper>,
+    fetch_error: Option<String>,
 
     courses_format: dump::courses::Format,
     courses: Option<Vec<Course>>,
     courses_path: String,
     courses_points: Option<(f32, f32)>,
+
+    schedule_format: dump::schedule::Format,
+    schedule: Option<Vec<Lesson>>,
+    schedule_path: String,
+    schedule_from: String,
+    schedule_to: String,
+
+    messages_format: dump::messages::Format,
+    messages: Option<Vec<Message>>,
+    messages_path: String,
+    messages_folders: [(MessageFolder, bool); 3],
+    messages_attachments_path: String,
+    messages_attachments_result: Option<Result<usize, String>>,
+
+    exams_format: dump::exams::Format,
+    exams: Option<Vec<Exam>>,
+    exams_path: String,
+
+    attendance_format: dump::attendance::Format,
+    attendance: Option<Vec<AttendanceMark>>,
+    attendance_path: String,
+
+    homework_format: dump::homework::Format,
+    homework: Option<Vec<HomeworkEntry>>,
+    homework_path: String,
+
+    news_format: dump::news::Format,
+    news: Option<Vec<NewsItem>>,
+    news_path: String,
+    news_dump_error: Option<String>,
+
+    groups_format: dump::groups::Format,
+    groups: Option<Vec<TeachingGroup>>,
+    groups_path: String,
+
+    tray_format: dump::tray::Format,
+    tray: Option<Vec<CourseOffering>>,
+    tray_path: String,
 }
 
 impl GuiApp {
     fn new(_cc: &eframe::CreationContext<'_>, ctx: InterfaceContext) -> Self {
         let (tx, rx) = channel();
+        let week = week_of(Local::now().date_naive());
         Self {
             ctx,
             rx,
@@ -95,16 +203,61 @@ impl GuiApp {
             wilma_roles: None,
             selected_wilma: None,
             logging_in: false,
+            loopback_redirect: false,
+            cancel_login: None,
+            login_username: String::new(),
+            login_password: String::new(),
+            login_error: None,
             dumper: None,
+            fetch_error: None,
             courses_format: dump::courses::Format::Json,
             courses: None,
             courses_path: String::new(),
             courses_points: None,
+            schedule_format: dump::schedule::Format::Json,
+            schedule: None,
+            schedule_path: String::new(),
+            schedule_from: week.start().to_string(),
+            schedule_to: week.end().to_string(),
+            messages_format: dump::messages::Format::Json,
+            messages: None,
+            messages_path: String::new(),
+            messages_folders: [
+                (MessageFolder::Inbox, true),
+                (MessageFolder::Sent, true),
+                (MessageFolder::Archive, true),
+            ],
+            messages_attachments_path: String::new(),
+            messages_attachments_result: None,
+            exams_format: dump::exams::Format::Json,
+            exams: None,
+            exams_path: String::new(),
+            attendance_format: dump::attendance::Format::Json,
+            attendance: None,
+            attendance_path: String::new(),
+            homework_format: dump::homework::Format::Json,
+            homework: None,
+            homework_path: String::new(),
+            news_format: dump::news::Format::Json,
+            news: None,
+            news_path: String::new(),
+            news_dump_error: None,
+            groups_format: dump::groups::Format::Csv,
+            groups: None,
+            groups_path: String::new(),
+            tray_format: dump::tray::Format::Json,
+            tray: None,
+            tray_path: String::new(),
         }
     }
 }
 
 impl eframe::App for GuiApp {
+    // eframe exits the process itself once the window closes
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        reg::release_handler();
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         match self.rx.try_recv() {
             Ok(AppMessage::WilmaList(wilma_list)) => {
@@ -115,6 +268,9 @@ impl eframe::App for GuiApp {
             }
             Ok(AppMessage::WilmaLogin(wilma)) => {
                 self.logging_in = false;
+                self.cancel_login = None;
+                self.login_password = String::new();
+                self.login_error = None;
                 self.selected_wilma = Some(*wilma);
 
                 let tx = self.tx.clone();
@@ -127,12 +283,56 @@ impl eframe::App for GuiApp {
                     ctx.request_repaint();
                 });
             }
+            Ok(AppMessage::WilmaLoginFailed(error)) => {
+                self.logging_in = false;
+                self.cancel_login = None;
+                self.login_error = Some(error);
+            }
             Ok(AppMessage::WilmaRoles(roles)) => {
                 self.wilma_roles = Some(roles);
             }
             Ok(AppMessage::WilmaCourses(courses)) => {
                 self.courses = Some(courses);
             }
+            Ok(AppMessage::WilmaSchedule(lessons)) => {
+                self.schedule = Some(lessons);
+            }
+            Ok(AppMessage::WilmaMessages(messages)) => {
+                self.messages = Some(messages);
+            }
+            Ok(AppMessage::WilmaAttachments(count)) => {
+                self.messages_attachments_result = Some(Ok(count));
+            }
+            Ok(AppMessage::WilmaAttachmentsFailed(error)) => {
+                self.messages_attachments_result = Some(Err(error));
+            }
+            Ok(AppMessage::WilmaExams(exams)) => {
+                self.exams = Some(exams);
+            }
+            Ok(AppMessage::WilmaAttendance(marks)) => {
+                self.attendance = Some(marks);
+            }
+            Ok(AppMessage::WilmaHomework(entries)) => {
+                self.homework = Some(entries);
+            }
+            Ok(AppMessage::WilmaNews(items)) => {
+                self.news = Some(items);
+            }
+            Ok(AppMessage::WilmaNewsDumped) => {
+                self.news_path = String::new();
+            }
+            Ok(AppMessage::WilmaNewsDumpFailed(error)) => {
+                self.news_dump_error = Some(error);
+            }
+            Ok(AppMessage::WilmaGroups(groups)) => {
+                self.groups = Some(groups);
+            }
+            Ok(AppMessage::WilmaTray(offerings)) => {
+                self.tray = Some(offerings);
+            }
+            Ok(AppMessage::WilmaFetchFailed(error)) => {
+                self.fetch_error = Some(error);
+            }
             Err(_) => {}
         }
 
@@ -145,9 +345,22 @@ impl eframe::App for GuiApp {
                 ui.heading("Wilma Dumper");
                 ui.separator();
 
+                if self.logging_in && self.cancel_login.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.label("Waiting for login in the browser...");
+                        if ui.button("Cancel").clicked() {
+                            if let Some(cancel) = self.cancel_login.take() {
+                                let _ = cancel.send(());
+                            }
+                        }
+                    });
+                    ui.separator();
+                }
+
                 ui.add_enabled_ui(!self.logging_in, |ui| match &self.selected_wilma {
                     Some(w) => {
                         wilma_status(w, ui);
+                        let authenticated = w.is_authenticated();
                         if ui.button("Change wilma").clicked() {
                             self.selected_wilma = None;
                             self.wilma_providers = None;
@@ -155,6 +368,7 @@ impl eframe::App for GuiApp {
                         if let Some(providers) = &self.wilma_providers {
                             ui.separator();
                             ui.label("Select openid provider:");
+                            ui.checkbox(&mut self.loopback_redirect, "Use loopback redirect");
                             for provider in providers {
                                 if ui.button(provider.name.clone()).clicked() {
                                     self.logging_in = true;
@@ -164,35 +378,103 @@ impl eframe::App for GuiApp {
                                     let mut wilma = self.selected_wilma.clone().unwrap();
                                     let client = self.ctx.client.clone();
                                     let provider = provider.clone();
+                                    let mode = match self.loopback_redirect {
+                                        true => RedirectMode::Loopback,
+                                        false => RedirectMode::ProtocolHandler,
+                                    };
+
+                                    let (cancel_tx, cancel_rx) = oneshot::channel();
+                                    self.cancel_login = Some(cancel_tx);
 
                                     tokio::spawn(async move {
-                                        wilma::auth::oauth_authorize(&client, &provider)
-                                            .await
-                                            .unwrap();
-                                        match ipc::receive_data().await.unwrap() {
-                                            IPCMessage::TokenResponse {
-                                                access_token,
-                                                id_token,
-                                            } => wilma
+                                        let login = async {
+                                            let token_data = wilma::auth::oauth_authorize(
+                                                &client, &provider, mode,
+                                            )
+                                            .await?;
+                                            wilma
                                                 .openid_login(
                                                     &client,
                                                     provider.configuration.clone(),
                                                     provider.client_id.clone(),
-                                                    access_token,
-                                                    id_token,
+                                                    token_data.access_token,
+                                                    token_data.id_token,
                                                 )
                                                 .await
-                                                .unwrap(),
-                                            _ => unreachable!(),
+                                        };
+                                        let result = wilma::auth::abortable(
+                                            login,
+                                            wilma::auth::DEFAULT_LOGIN_TIMEOUT,
+                                            async {
+                                                let _ = cancel_rx.await;
+                                            },
+                                        )
+                                        .await;
+
+                                        let message = match result {
+                                            Ok(()) => {
+                                                wilma.set_relogin(openid_relogin(
+                                                    client, provider, mode,
+                                                ));
+                                                AppMessage::WilmaLogin(Box::new(wilma))
+                                            }
+                                            Err(e) => AppMessage::WilmaLoginFailed(e.to_string()),
                                         };
 
-                                        tx.send(AppMessage::WilmaLogin(Box::new(wilma))).unwrap();
+                                        tx.send(message).unwrap();
                                         ctx.request_repaint();
                                     });
                                 }
                             }
                         }
 
+                        if !authenticated {
+                            ui.separator();
+                            ui.label("Log in with Wilma username and password:");
+                            ui.horizontal(|ui| {
+                                ui.label("Username");
+                                ui.text_edit_singleline(&mut self.login_username);
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Password");
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut self.login_password)
+                                        .password(true),
+                                );
+                            });
+                            if ui.button("Log in").clicked() {
+                                self.logging_in = true;
+
+                                let tx = self.tx.clone();
+                                let ctx = ctx.clone();
+                                let mut wilma = self.selected_wilma.clone().unwrap();
+                                let client = self.ctx.client.clone();
+                                let username = self.login_username.clone();
+                                let password = self.login_password.clone();
+
+                                tokio::spawn(async move {
+                                    let message = match wilma
+                                        .password_login(&client, username.clone(), password.clone())
+                                        .await
+                                    {
+                                        Ok(()) => {
+                                            wilma.set_relogin(password_relogin(
+                                                client, username, password,
+                                            ));
+                                            AppMessage::WilmaLogin(Box::new(wilma))
+                                        }
+                                        Err(e) => AppMessage::WilmaLoginFailed(e.to_string()),
+                                    };
+
+                                    tx.send(message).unwrap();
+                                    ctx.request_repaint();
+                                });
+                            }
+                            if let Some(error) = &self.login_error {
+                                ui.colored_label(egui::Color32::RED, error);
+                            }
+                        }
+
                         if let Some(roles) = &self.wilma_roles {
                             ui.separator();
                             ui.label("Select role:");
@@ -223,14 +505,38 @@ impl eframe::App for GuiApp {
                     self.wilma_providers = None;
                 }
                 ui.separator();
+                let role = self.selected_wilma.as_ref().and_then(|w| w.role.as_ref());
+                if !self.dumper.as_ref().map_or(true, |d| d.supports(role)) {
+                    self.dumper = None;
+                }
+                let selected = self.dumper.as_ref().map_or("".into(), |d| d.to_string());
                 egui::ComboBox::from_label("Select dumper")
-                    .selected_text(self.dumper.as_ref().map_or("".into(), |d| d.to_string()))
+                    .selected_text(selected)
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(&mut self.dumper, Some(Dumper::Courses), "Courses");
+                        for dumper in Dumper::ALL.into_iter().filter(|d| d.supports(role)) {
+                            let label = dumper.to_string();
+                            if ui
+                                .selectable_value(&mut self.dumper, Some(dumper), label)
+                                .changed()
+                            {
+                                self.fetch_error = None;
+                            }
+                        }
                     });
+                if let Some(error) = &self.fetch_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
                 ui.separator();
                 match &self.dumper {
                     Some(Dumper::Courses) => courses_dumper(self, ctx, ui),
+                    Some(Dumper::Schedule) => schedule_dumper(self, ctx, ui),
+                    Some(Dumper::Messages) => messages_dumper(self, ctx, ui),
+                    Some(Dumper::Exams) => exams_dumper(self, ctx, ui),
+                    Some(Dumper::Attendance) => attendance_dumper(self, ctx, ui),
+                    Some(Dumper::Homework) => homework_dumper(self, ctx, ui),
+                    Some(Dumper::News) => news_dumper(self, ctx, ui),
+                    Some(Dumper::Groups) => groups_dumper(self, ctx, ui),
+                    Some(Dumper::Tray) => tray_dumper(self, ctx, ui),
                     None => {}
                 }
             });
@@ -248,20 +554,24 @@ fn courses_dumper(app: &mut GuiApp, ctx: &egui::Context, ui: &mut Ui) {
         })
         .clicked()
     {
+        app.fetch_error = None;
         let tx = app.tx.clone();
         let ctx = ctx.clone();
         let client = app.ctx.client.clone();
         let wilma = app.selected_wilma.as_ref().unwrap().clone();
         tokio::spawn(async move {
-            let courses = wilma.get_courses(&client).await.unwrap();
-            tx.send(AppMessage::WilmaCourses(courses)).unwrap();
+            let message = match wilma.get_courses(&client).await {
+                Ok(courses) => AppMessage::WilmaCourses(courses),
+                Err(e) => AppMessage::WilmaFetchFailed(e.to_string()),
+            };
+            tx.send(message).unwrap();
             ctx.request_repaint();
         });
     }
     ui.vertical(|ui| {
         ui.group(|ui| {
             egui::ComboBox::from_label("Select format")
-                .selected_text(app.courses_format.clone().to_string())
+                .selected_text(dump::format_name(&app.courses_format))
                 .show_ui(ui, |ui| {
                     ui.selectable_value(
                         &mut app.courses_format,
@@ -297,8 +607,7 @@ fn courses_dumper(app: &mut GuiApp, ctx: &egui::Context, ui: &mut Ui) {
                     ))
                 }
             });
-            if app.courses_points.is_some() {
-                let (total, earned) = app.courses_points.as_ref().unwrap();
+            if let Some((total, earned)) = &app.courses_points {
                 ui.label(format!("Not yet earned: {}", total - earned));
                 ui.label(format!("Earned: {earned}",));
                 ui.label(format!("Selected and earned: {total}"));
@@ -307,6 +616,664 @@ fn courses_dumper(app: &mut GuiApp, ctx: &egui::Context, ui: &mut Ui) {
     });
 }
 
+fn schedule_dumper(app: &mut GuiApp, ctx: &egui::Context, ui: &mut Ui) {
+    ui.heading("Schedule dumper");
+    ui.horizontal(|ui| {
+        ui.label("From");
+        ui.text_edit_singleline(&mut app.schedule_from);
+        ui.label("To");
+        ui.text_edit_singleline(&mut app.schedule_to);
+    });
+    let range = match (
+        app.schedule_from.parse::<NaiveDate>(),
+        app.schedule_to.parse::<NaiveDate>(),
+    ) {
+        (Ok(from), Ok(to)) if from <= to => Some(from..=to),
+        _ => None,
+    };
+    if range.is_none() {
+        ui.label("Dates must be given as YYYY-MM-DD");
+    }
+    ui.add_enabled_ui(range.is_some(), |ui| {
+        if ui
+            .button(if app.schedule.is_some() {
+                "Re-fetch schedule"
+            } else {
+                "Fetch schedule"
+            })
+            .clicked()
+        {
+            app.fetch_error = None;
+            let tx = app.tx.clone();
+            let ctx = ctx.clone();
+            let client = app.ctx.client.clone();
+            let wilma = app.selected_wilma.as_ref().unwrap().clone();
+            let range = range.clone().unwrap();
+            tokio::spawn(async move {
+                let message = match wilma.get_schedule(&client, range).await {
+                    Ok(lessons) => AppMessage::WilmaSchedule(lessons),
+                    Err(e) => AppMessage::WilmaFetchFailed(e.to_string()),
+                };
+                tx.send(message).unwrap();
+                ctx.request_repaint();
+            });
+        }
+    });
+    if let Some(lessons) = &app.schedule {
+        ui.label(format!("{} lessons", lessons.len()));
+    }
+    ui.group(|ui| {
+        egui::ComboBox::from_label("Select format")
+            .selected_text(dump::format_name(&app.schedule_format))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(
+                    &mut app.schedule_format,
+                    dump::schedule::Format::Json,
+                    "Json",
+                );
+                ui.selectable_value(&mut app.schedule_format, dump::schedule::Format::Csv, "Csv");
+                ui.selectable_value(&mut app.schedule_format, dump::schedule::Format::Ics, "Ics");
+            });
+        ui.horizontal(|ui| {
+            ui.label("File path");
+            ui.text_edit_singleline(&mut app.schedule_path);
+        });
+        ui.add_enabled_ui(app.schedule.is_some(), |ui| {
+            if ui.button("Dump").clicked() {
+                if let Ok(file) = std::fs::File::create(&app.schedule_path) {
+                    dump::schedule::dump_to_writer(
+                        app.schedule.as_ref().unwrap(),
+                        app.selected_wilma.as_ref().unwrap(),
+                        file,
+                        app.schedule_format.clone(),
+                    )
+                    .unwrap();
+                    app.schedule_path = String::new();
+                }
+            }
+        })
+    });
+}
+
+fn messages_dumper(app: &mut GuiApp, ctx: &egui::Context, ui: &mut Ui) {
+    ui.heading("Message dumper");
+    ui.horizontal(|ui| {
+        for (folder, selected) in app.messages_folders.iter_mut() {
+            ui.checkbox(selected, format!("{folder:?}"));
+        }
+    });
+    let folders = app
+        .messages_folders
+        .iter()
+        .filter(|(_, selected)| *selected)
+        .map(|(folder, _)| *folder)
+        .collect::<Vec<_>>();
+    ui.add_enabled_ui(!folders.is_empty(), |ui| {
+        if ui
+            .button(if app.messages.is_some() {
+                "Re-fetch messages"
+            } else {
+                "Fetch messages"
+            })
+            .clicked()
+        {
+            app.fetch_error = None;
+            let tx = app.tx.clone();
+            let ctx = ctx.clone();
+            let client = app.ctx.client.clone();
+            let wilma = app.selected_wilma.as_ref().unwrap().clone();
+            tokio::spawn(async move {
+                let message = match wilma::api::messages::get_full_messages(
+                    &client, &wilma, &folders,
+                )
+                .await
+                {
+                    Ok(messages) => AppMessage::WilmaMessages(messages),
+                    Err(e) => AppMessage::WilmaFetchFailed(e.to_string()),
+                };
+                tx.send(message).unwrap();
+                ctx.request_repaint();
+            });
+        }
+    });
+    if let Some(messages) = &app.messages {
+        ui.label(format!("{} messages", messages.len()));
+    }
+    ui.group(|ui| {
+        egui::ComboBox::from_label("Select format")
+            .selected_text(dump::format_name(&app.messages_format))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(
+                    &mut app.messages_format,
+                    dump::messages::Format::Json,
+                    "Json",
+                );
+                ui.selectable_value(
+                    &mut app.messages_format,
+                    dump::messages::Format::Mbox,
+                    "Mbox",
+                );
+                ui.selectable_value(
+                    &mut app.messages_format,
+                    dump::messages::Format::Maildir,
+                    "Maildir",
+                );
+            });
+        ui.horizontal(|ui| {
+            ui.label(match app.messages_format {
+                dump::messages::Format::Maildir => "Directory path",
+                _ => "File path",
+            });
+            ui.text_edit_singleline(&mut app.messages_path);
+        });
+        ui.add_enabled_ui(app.messages.is_some(), |ui| {
+            if ui.button("Dump").clicked() {
+                let messages = app.messages.as_ref().unwrap();
+                let wilma = app.selected_wilma.as_ref().unwrap();
+                let dumped = match app.messages_format {
+                    dump::messages::Format::Maildir => dump::messages::dump_to_maildir(
+                        messages,
+                        wilma,
+                        Path::new(&app.messages_path),
+                    )
+                    .is_ok(),
+                    _ => match std::fs::File::create(&app.messages_path) {
+                        Ok(file) => {
+                            dump::messages::dump_to_writer(
+                                messages,
+                                wilma,
+                                file,
+                                app.messages_format.clone(),
+                            )
+                            .unwrap();
+                            true
+                        }
+                        Err(_) => false,
+                    },
+                };
+                if dumped {
+                    app.messages_path = String::new();
+                }
+            }
+        })
+    });
+    ui.group(|ui| {
+        ui.horizontal(|ui| {
+            ui.label("Attachment directory");
+            ui.text_edit_singleline(&mut app.messages_attachments_path);
+        });
+        ui.add_enabled_ui(app.messages.is_some(), |ui| {
+            if ui.button("Download attachments").clicked() {
+                let tx = app.tx.clone();
+                let ctx = ctx.clone();
+                let messages = app.messages.clone().unwrap();
+                let client = app.ctx.client.clone();
+                let wilma = app.selected_wilma.as_ref().unwrap().clone();
+                let dir = app.messages_attachments_path.clone();
+                app.messages_attachments_result = None;
+                tokio::spawn(async move {
+                    let downloaded = async {
+                        let attachments =
+                            download::message_attachments(&messages, &wilma.get_url()?);
+                        Downloader::new(dir, DEFAULT_DOWNLOAD_LIMIT)
+                            .download(&client, &wilma, attachments)
+                            .await
+                    };
+                    let message = match downloaded.await {
+                        Ok(entries) => AppMessage::WilmaAttachments(entries.len()),
+                        Err(e) => AppMessage::WilmaAttachmentsFailed(e.to_string()),
+                    };
+                    tx.send(message).unwrap();
+                    ctx.request_repaint();
+                });
+            }
+        });
+        match &app.messages_attachments_result {
+            Some(Ok(count)) => {
+                ui.label(format!("{count} attachments downloaded"));
+            }
+            Some(Err(error)) => {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            None => {}
+        }
+    });
+}
+
+fn exams_dumper(app: &mut GuiApp, ctx: &egui::Context, ui: &mut Ui) {
+    ui.heading("Exam dumper");
+    if ui
+        .button(if app.exams.is_some() {
+            "Re-fetch exams"
+        } else {
+            "Fetch exams"
+        })
+        .clicked()
+    {
+        app.fetch_error = None;
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let client = app.ctx.client.clone();
+        let wilma = app.selected_wilma.as_ref().unwrap().clone();
+        tokio::spawn(async move {
+            let message = match wilma.get_exams(&client).await {
+                Ok(exams) => AppMessage::WilmaExams(exams),
+                Err(e) => AppMessage::WilmaFetchFailed(e.to_string()),
+            };
+            tx.send(message).unwrap();
+            ctx.request_repaint();
+        });
+    }
+    if let Some(exams) = &app.exams {
+        ui.label(format!("{} exams", exams.len()));
+    }
+    ui.group(|ui| {
+        egui::ComboBox::from_label("Select format")
+            .selected_text(dump::format_name(&app.exams_format))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut app.exams_format, dump::exams::Format::Json, "Json");
+                ui.selectable_value(&mut app.exams_format, dump::exams::Format::Csv, "Csv");
+            });
+        ui.horizontal(|ui| {
+            ui.label("File path");
+            ui.text_edit_singleline(&mut app.exams_path);
+        });
+        ui.add_enabled_ui(app.exams.is_some(), |ui| {
+            if ui.button("Dump").clicked() {
+                if let Ok(file) = std::fs::File::create(&app.exams_path) {
+                    dump::exams::dump_to_writer(
+                        app.exams.as_ref().unwrap(),
+                        file,
+                        app.exams_format.clone(),
+                    )
+                    .unwrap();
+                    app.exams_path = String::new();
+                }
+            }
+        })
+    });
+}
+
+fn attendance_dumper(app: &mut GuiApp, ctx: &egui::Context, ui: &mut Ui) {
+    ui.heading("Attendance dumper");
+    if ui
+        .button(if app.attendance.is_some() {
+            "Re-fetch attendance"
+        } else {
+            "Fetch attendance"
+        })
+        .clicked()
+    {
+        app.fetch_error = None;
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let client = app.ctx.client.clone();
+        let wilma = app.selected_wilma.as_ref().unwrap().clone();
+        tokio::spawn(async move {
+            let message = match wilma.get_attendance(&client).await {
+                Ok(marks) => AppMessage::WilmaAttendance(marks),
+                Err(e) => AppMessage::WilmaFetchFailed(e.to_string()),
+            };
+            tx.send(message).unwrap();
+            ctx.request_repaint();
+        });
+    }
+    ui.vertical(|ui| {
+        ui.group(|ui| {
+            egui::ComboBox::from_label("Select format")
+                .selected_text(dump::format_name(&app.attendance_format))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut app.attendance_format,
+                        dump::attendance::Format::Json,
+                        "Json",
+                    );
+                    ui.selectable_value(
+                        &mut app.attendance_format,
+                        dump::attendance::Format::Csv,
+                        "Csv",
+                    );
+                });
+            ui.horizontal(|ui| {
+                ui.label("File path");
+                ui.text_edit_singleline(&mut app.attendance_path);
+            });
+            ui.add_enabled_ui(app.attendance.is_some(), |ui| {
+                if ui.button("Dump").clicked() {
+                    if let Ok(file) = std::fs::File::create(&app.attendance_path) {
+                        dump::attendance::dump_to_writer(
+                            app.attendance.as_ref().unwrap(),
+                            file,
+                            app.attendance_format.clone(),
+                        )
+                        .unwrap();
+                        app.attendance_path = String::new();
+                    }
+                }
+            })
+        });
+        if let Some(marks) = &app.attendance {
+            ui.label("Marks per course");
+            for s in dump::attendance::summarize_by_course(marks) {
+                ui.label(format!(
+                    "{}: {} excused, {} unexcused, {} late, {} other",
+                    s.name, s.excused, s.unexcused, s.late, s.other
+                ));
+            }
+        }
+    });
+}
+
+fn homework_dumper(app: &mut GuiApp, ctx: &egui::Context, ui: &mut Ui) {
+    ui.heading("Homework dumper");
+    if ui
+        .button(if app.homework.is_some() {
+            "Re-fetch homework"
+        } else {
+            "Fetch homework"
+        })
+        .clicked()
+    {
+        app.fetch_error = None;
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let client = app.ctx.client.clone();
+        let wilma = app.selected_wilma.as_ref().unwrap().clone();
+        tokio::spawn(async move {
+            let message = match wilma.get_homework(&client).await {
+                Ok(entries) => AppMessage::WilmaHomework(entries),
+                Err(e) => AppMessage::WilmaFetchFailed(e.to_string()),
+            };
+            tx.send(message).unwrap();
+            ctx.request_repaint();
+        });
+    }
+    if let Some(entries) = &app.homework {
+        ui.label(format!("{} entries", entries.len()));
+    }
+    ui.group(|ui| {
+        egui::ComboBox::from_label("Select format")
+            .selected_text(dump::format_name(&app.homework_format))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(
+                    &mut app.homework_format,
+                    dump::homework::Format::Json,
+                    "Json",
+                );
+                ui.selectable_value(&mut app.homework_format, dump::homework::Format::Csv, "Csv");
+                ui.selectable_value(
+                    &mut app.homework_format,
+                    dump::homework::Format::Markdown,
+                    "Markdown",
+                );
+            });
+        ui.horizontal(|ui| {
+            ui.label("File path");
+            ui.text_edit_singleline(&mut app.homework_path);
+        });
+        ui.add_enabled_ui(app.homework.is_some(), |ui| {
+            if ui.button("Dump").clicked() {
+                if let Ok(file) = std::fs::File::create(&app.homework_path) {
+                    dump::homework::dump_to_writer(
+                        app.homework.as_ref().unwrap(),
+                        file,
+                        app.homework_format.clone(),
+                    )
+                    .unwrap();
+                    app.homework_path = String::new();
+                }
+            }
+        })
+    });
+}
+
+fn news_dumper(app: &mut GuiApp, ctx: &egui::Context, ui: &mut Ui) {
+    ui.heading("News dumper");
+    if ui
+        .button(if app.news.is_some() {
+            "Re-fetch news"
+        } else {
+            "Fetch news"
+        })
+        .clicked()
+    {
+        app.fetch_error = None;
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let client = app.ctx.client.clone();
+        let wilma = app.selected_wilma.as_ref().unwrap().clone();
+        tokio::spawn(async move {
+            let message = match wilma::api::news::get_full_news(&client, &wilma).await {
+                Ok(items) => AppMessage::WilmaNews(items),
+                Err(e) => AppMessage::WilmaFetchFailed(e.to_string()),
+            };
+            tx.send(message).unwrap();
+            ctx.request_repaint();
+        });
+    }
+    if let Some(items) = &app.news {
+        ui.label(format!("{} news items", items.len()));
+    }
+    ui.group(|ui| {
+        egui::ComboBox::from_label("Select format")
+            .selected_text(dump::format_name(&app.news_format))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut app.news_format, dump::news::Format::Json, "Json");
+                ui.selectable_value(
+                    &mut app.news_format,
+                    dump::news::Format::Markdown,
+                    "Markdown",
+                );
+            });
+        ui.horizontal(|ui| {
+            ui.label(match app.news_format {
+                dump::news::Format::Markdown => "Directory path",
+                _ => "File path",
+            });
+            ui.text_edit_singleline(&mut app.news_path);
+        });
+        ui.add_enabled_ui(app.news.is_some(), |ui| {
+            if ui.button("Dump").clicked() {
+                match app.news_format {
+                    //attachments are downloaded while dumping
+                    dump::news::Format::Markdown => {
+                        let tx = app.tx.clone();
+                        let ctx = ctx.clone();
+                        let items = app.news.clone().unwrap();
+                        let client = app.ctx.client.clone();
+                        let wilma = app.selected_wilma.as_ref().unwrap().clone();
+                        let path = app.news_path.clone();
+                        app.news_dump_error = None;
+                        tokio::spawn(async move {
+                            let message = match dump::news::dump_to_directory(
+                                &items,
+                                &client,
+                                &wilma,
+                                Path::new(&path),
+                                DEFAULT_DOWNLOAD_LIMIT,
+                            )
+                            .await
+                            {
+                                Ok(()) => AppMessage::WilmaNewsDumped,
+                                Err(e) => AppMessage::WilmaNewsDumpFailed(e.to_string()),
+                            };
+                            tx.send(message).unwrap();
+                            ctx.request_repaint();
+                        });
+                    }
+                    _ => {
+                        if let Ok(file) = std::fs::File::create(&app.news_path) {
+                            dump::news::dump_to_writer(
+                                app.news.as_ref().unwrap(),
+                                file,
+                                app.news_format.clone(),
+                            )
+                            .unwrap();
+                            app.news_path = String::new();
+                        }
+                    }
+                }
+            }
+        });
+        if let Some(error) = &app.news_dump_error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    });
+}
+
+fn groups_dumper(app: &mut GuiApp, ctx: &egui::Context, ui: &mut Ui) {
+    ui.heading("Teaching group dumper");
+    if ui
+        .button(if app.groups.is_some() {
+            "Re-fetch groups"
+        } else {
+            "Fetch groups"
+        })
+        .clicked()
+    {
+        app.fetch_error = None;
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let client = app.ctx.client.clone();
+        let wilma = app.selected_wilma.as_ref().unwrap().clone();
+        tokio::spawn(async move {
+            let message = match wilma.get_groups(&client).await {
+                Ok(groups) => AppMessage::WilmaGroups(groups),
+                Err(e) => AppMessage::WilmaFetchFailed(e.to_string()),
+            };
+            tx.send(message).unwrap();
+            ctx.request_repaint();
+        });
+    }
+    if let Some(groups) = &app.groups {
+        for g in groups {
+            ui.label(format!(
+                "{} {}: {} students",
+                g.code,
+                g.course,
+                g.students.len()
+            ));
+        }
+    }
+    ui.group(|ui| {
+        egui::ComboBox::from_label("Select format")
+            .selected_text(dump::format_name(&app.groups_format))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut app.groups_format, dump::groups::Format::Csv, "Csv");
+                ui.selectable_value(&mut app.groups_format, dump::groups::Format::Json, "Json");
+            });
+        ui.horizontal(|ui| {
+            ui.label("File path");
+            ui.text_edit_singleline(&mut app.groups_path);
+        });
+        ui.add_enabled_ui(app.groups.is_some(), |ui| {
+            if ui.button("Dump").clicked() {
+                if let Ok(file) = std::fs::File::create(&app.groups_path) {
+                    dump::groups::dump_to_writer(
+                        app.groups.as_ref().unwrap(),
+                        file,
+                        app.groups_format.clone(),
+                    )
+                    .unwrap();
+                    app.groups_path = String::new();
+                }
+            }
+        })
+    });
+}
+
+fn tray_dumper(app: &mut GuiApp, ctx: &egui::Context, ui: &mut Ui) {
+    ui.heading("Course tray dumper");
+    if ui
+        .button(if app.tray.is_some() {
+            "Re-fetch course tray"
+        } else {
+            "Fetch course tray"
+        })
+        .clicked()
+    {
+        app.fetch_error = None;
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let client = app.ctx.client.clone();
+        let wilma = app.selected_wilma.as_ref().unwrap().clone();
+        tokio::spawn(async move {
+            let message = match wilma.get_course_tray(&client).await {
+                Ok(offerings) => AppMessage::WilmaTray(offerings),
+                Err(e) => AppMessage::WilmaFetchFailed(e.to_string()),
+            };
+            tx.send(message).unwrap();
+            ctx.request_repaint();
+        });
+    }
+    if let Some(offerings) = &app.tray {
+        ui.label(format!("{} groups on offer", offerings.len()));
+    }
+    ui.group(|ui| {
+        egui::ComboBox::from_label("Select format")
+            .selected_text(dump::format_name(&app.tray_format))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut app.tray_format, dump::tray::Format::Json, "Json");
+                ui.selectable_value(&mut app.tray_format, dump::tray::Format::Csv, "Csv");
+            });
+        ui.horizontal(|ui| {
+            ui.label("File path");
+            ui.text_edit_singleline(&mut app.tray_path);
+        });
+        ui.add_enabled_ui(app.tray.is_some(), |ui| {
+            if ui.button("Dump").clicked() {
+                if let Ok(file) = std::fs::File::create(&app.tray_path) {
+                    dump::tray::dump_to_writer(
+                        app.tray.as_ref().unwrap(),
+                        file,
+                        app.tray_format.clone(),
+                    )
+                    .unwrap();
+                    app.tray_path = String::new();
+                }
+            }
+        })
+    });
+}
+
+/// Logs in again through the browser when the session expires mid dump
+fn openid_relogin(client: Client, provider: OpenIDProvider, mode: RedirectMode) -> ReloginCallback {
+    ReloginCallback::new(move |mut wilma| {
+        let client = client.clone();
+        let provider = provider.clone();
+        Box::pin(async move {
+            let token_data = wilma::auth::abortable(
+                wilma::auth::oauth_authorize(&client, &provider, mode),
+                wilma::auth::DEFAULT_LOGIN_TIMEOUT,
+                std::future::pending(),
+            )
+            .await?;
+            wilma
+                .openid_login(
+                    &client,
+                    provider.configuration.clone(),
+                    provider.client_id.clone(),
+                    token_data.access_token,
+                    token_data.id_token,
+                )
+                .await?;
+            Ok(wilma)
+        })
+    })
+}
+
+fn password_relogin(client: Client, username: String, password: String) -> ReloginCallback {
+    ReloginCallback::new(move |mut wilma| {
+        let client = client.clone();
+        let username = username.clone();
+        let password = password.clone();
+        Box::pin(async move {
+            wilma.password_login(&client, username, password).await?;
+            Ok(wilma)
+        })
+    })
+}
+
 fn wilma_status(w: &Wilma, ui: &mut Ui) {
     ui.label(format!("Selected Wilma: {}", w.name));
     ui.label(format!("Logged in: {}", w.is_authenticated()));
@@ -5,7 +5,7 @@ use tokio::runtime::Handle;
 mod cli;
 mod gui;
 
-pub use cli::CliInterface;
+pub use cli::{requested_gui, CliInterface};
 pub use gui::GuiInterface;
 
 pub struct InterfaceContext {
@@ -0,0 +1,259 @@
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{anyhow, ensure, Context, Result};
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time;
+
+use sha2::{Digest, Sha256};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{from_slice, to_vec};
+
+use log::*;
+
+#[cfg(unix)]
+mod unix;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(unix)]
+use unix::UnixSocket as PlatformTransport;
+#[cfg(windows)]
+use windows::NamedPipe as PlatformTransport;
+
+/// Bumped whenever `IPCMessage` or the framing changes, so a stale protocol handler
+/// pointing at an older exe fails loudly instead of sending garbage
+const PROTOCOL_VERSION: u16 = 2;
+const MAX_FRAME_SIZE: u32 = 1024 * 1024;
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+const SECRET_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A single login attempt. The name is derived from the oauth state so the protocol handler can
+/// find it, the secret is random and handed over in a file only this user can read
+pub struct Channel {
+    name: String,
+    secret: [u8; 32],
+    // removed again once the login attempt is over
+    secret_file: Option<PathBuf>,
+}
+
+impl Channel {
+    /// Starts a login attempt, writing a fresh secret for the protocol handler
+    pub fn create(state: &str) -> Result<Self> {
+        let name = get_name(state);
+        let secret: [u8; 32] = rand::random();
+
+        let path = get_secret_path(&name)?;
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        trace!("Writing IPC secret to {path:?}");
+        options.open(&path)?.write_all(&secret)?;
+
+        Ok(Self {
+            name,
+            secret,
+            secret_file: Some(path),
+        })
+    }
+
+    /// Joins the login attempt the main process started for this state
+    pub fn open(state: &str) -> Result<Self> {
+        let name = get_name(state);
+        let path = get_secret_path(&name)?;
+        let secret = fs::read(&path)
+            .with_context(|| format!("No login waiting for this redirect ({path:?})"))?
+            .try_into()
+            .map_err(|_| anyhow!("Malformed IPC secret file"))?;
+
+        Ok(Self {
+            name,
+            secret,
+            secret_file: None,
+        })
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        if let Some(path) = &self.secret_file {
+            if let Err(e) = fs::remove_file(path) {
+                debug!("Could not remove IPC secret {path:?}: {e}");
+            }
+        }
+    }
+}
+
+fn get_name(state: &str) -> String {
+    let name = Sha256::new()
+        .chain_update("wilma-dumper ipc name")
+        .chain_update(state)
+        .finalize();
+
+    name[..8].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Secrets go under the runtime dir where there is one, otherwise the local data dir, both
+/// private to the user
+fn get_secret_path(name: &str) -> Result<PathBuf> {
+    let dir = dirs::runtime_dir()
+        .or_else(dirs::data_local_dir)
+        .ok_or_else(|| anyhow!("Could not find a directory for the IPC secret"))?
+        .join(env!("CARGO_PKG_NAME"));
+
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&dir)?;
+
+    Ok(dir.join(format!("{name}.secret")))
+}
+
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// Connects the main process and the protocol handler process
+#[async_trait]
+pub trait Transport {
+    /// Waits for the other side to connect
+    async fn accept(&self, name: &str) -> Result<Box<dyn Stream>>;
+    /// Waits for the accepting side to be up and connects to it
+    async fn connect(&self, name: &str) -> Result<Box<dyn Stream>>;
+}
+
+#[derive(Deserialize, Serialize)]
+pub enum IPCMessage {
+    TokenRequest {
+        state: String,
+        client_id: String,
+        token_endpoint: String,
+        code_verifier: String,
+    },
+
+    TokenResponse {
+        access_token: String,
+        id_token: String,
+    },
+
+    Error {
+        message: String,
+    },
+}
+
+/// Frame is a big endian u16 protocol version and u32 payload length followed by the payload
+async fn read_frame(stream: &mut dyn Stream) -> Result<Vec<u8>> {
+    let version = stream.read_u16().await?;
+    ensure!(
+        version == PROTOCOL_VERSION,
+        "IPC protocol version mismatch, got {version} but expected {PROTOCOL_VERSION}"
+    );
+
+    let length = stream.read_u32().await?;
+    ensure!(
+        length <= MAX_FRAME_SIZE,
+        "IPC frame too large ({length} bytes)"
+    );
+
+    let mut buf = vec![0; length as usize];
+    stream.read_exact(&mut buf).await?;
+
+    Ok(buf)
+}
+
+async fn write_frame(stream: &mut dyn Stream, data: &[u8]) -> Result<()> {
+    let length = u32::try_from(data.len())
+        .ok()
+        .filter(|l| *l <= MAX_FRAME_SIZE)
+        .ok_or_else(|| anyhow!("IPC frame too large ({} bytes)", data.len()))?;
+
+    stream.write_u16(PROTOCOL_VERSION).await?;
+    stream.write_u32(length).await?;
+    stream.write_all(data).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+async fn read_secret(stream: &mut dyn Stream, secret: &[u8; 32]) -> Result<()> {
+    let mut given = [0; 32];
+    time::timeout(SECRET_TIMEOUT, stream.read_exact(&mut given))
+        .await
+        .context("Timed out waiting for the IPC secret")??;
+
+    // constant time so the secret can't be guessed byte by byte
+    let diff = given
+        .iter()
+        .zip(secret.iter())
+        .fold(0, |acc, (a, b)| acc | (a ^ b));
+    ensure!(diff == 0, "IPC peer presented the wrong secret");
+
+    Ok(())
+}
+
+async fn write_secret(stream: &mut dyn Stream, secret: &[u8; 32]) -> Result<()> {
+    stream.write_all(secret).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+/// An authenticated connection from the other process, which waits for an answer from here on
+pub struct Connection(Box<dyn Stream>);
+
+impl Connection {
+    pub async fn receive(mut self) -> Result<IPCMessage> {
+        let data = read_frame(&mut *self.0).await?;
+
+        trace!("IPC received {} bytes", data.len());
+
+        Ok(from_slice::<IPCMessage>(&data)?)
+    }
+}
+
+/// Waits for the other process to connect and prove it knows the secret
+pub async fn accept(channel: &Channel) -> Result<Connection> {
+    trace!("IPC attempting to receive data");
+    let mut stream = loop {
+        let mut stream = PlatformTransport.accept(&channel.name).await?;
+        match read_secret(&mut *stream, &channel.secret).await {
+            Ok(()) => break stream,
+            Err(e) => warn!("Rejected IPC connection: {e:#}"),
+        }
+    };
+    write_secret(&mut *stream, &channel.secret).await?;
+
+    Ok(Connection(stream))
+}
+
+pub async fn receive_data(channel: &Channel) -> Result<IPCMessage> {
+    accept(channel).await?.receive().await
+}
+
+pub async fn send_data(channel: &Channel, data: IPCMessage) -> Result<()> {
+    trace!("IPC attempting to send data");
+    let mut stream = PlatformTransport.connect(&channel.name).await?;
+    write_secret(&mut *stream, &channel.secret).await?;
+    read_secret(&mut *stream, &channel.secret).await?;
+
+    let data = to_vec(&data)?;
+    write_frame(&mut *stream, &data).await?;
+
+    trace!("IPC sent {} bytes of data", data.len());
+
+    Ok(())
+}
+
+/// Answers a received message. The other process is already waiting, so connecting is given up
+/// after `REPLY_TIMEOUT` instead of retrying forever
+pub async fn send_reply(channel: &Channel, data: IPCMessage) -> Result<()> {
+    time::timeout(REPLY_TIMEOUT, send_data(channel, data))
+        .await
+        .context("Timed out sending IPC reply")?
+}
@@ -0,0 +1,84 @@
+use std::fs::{self, DirBuilder, Permissions};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tokio::net::{UnixListener, UnixStream};
+use tokio::time;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use log::*;
+
+use super::{Stream, Transport};
+
+pub struct UnixSocket;
+
+/// Removes the socket file when accepting finishes or is cancelled
+struct SocketGuard(PathBuf);
+
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.0) {
+            debug!("Could not remove socket {:?}: {e}", self.0);
+        }
+    }
+}
+
+/// `$XDG_RUNTIME_DIR` is already private to the user, otherwise falls back to a private
+/// directory under the temp dir
+fn get_socket_path(name: &str) -> Result<PathBuf> {
+    let socket_name = format!("{}-{name}.sock", env!("CARGO_PKG_NAME"));
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(dir).join(socket_name));
+    }
+
+    let dir = std::env::temp_dir().join(format!("{}-{}", env!("CARGO_PKG_NAME"), get_username()));
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e.into()),
+    }
+    fs::set_permissions(&dir, Permissions::from_mode(0o700))?;
+
+    Ok(dir.join(socket_name))
+}
+
+fn get_username() -> String {
+    std::env::var("USER").unwrap_or_else(|_| "user".to_string())
+}
+
+#[async_trait]
+impl Transport for UnixSocket {
+    async fn accept(&self, name: &str) -> Result<Box<dyn Stream>> {
+        let path = get_socket_path(name)?;
+        match fs::remove_file(&path) {
+            Ok(()) => debug!("Removed stale socket {path:?}"),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        let _guard = SocketGuard(path.clone());
+        fs::set_permissions(&path, Permissions::from_mode(0o600))?;
+
+        let (stream, _) = listener.accept().await?;
+
+        Ok(Box::new(stream))
+    }
+
+    async fn connect(&self, name: &str) -> Result<Box<dyn Stream>> {
+        let path = get_socket_path(name)?;
+        let stream = loop {
+            if let Ok(stream) = UnixStream::connect(&path).await {
+                break stream;
+            }
+
+            time::sleep(Duration::from_millis(50)).await;
+        };
+
+        Ok(Box::new(stream))
+    }
+}
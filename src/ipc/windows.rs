@@ -0,0 +1,38 @@
+use std::time::Duration;
+use tokio::net::windows::named_pipe::{ClientOptions, ServerOptions};
+use tokio::time;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{Stream, Transport};
+
+const PIPE_PREFIX: &str = r"\\.\pipe\wilma-dumper-";
+
+pub struct NamedPipe;
+
+#[async_trait]
+impl Transport for NamedPipe {
+    async fn accept(&self, name: &str) -> Result<Box<dyn Stream>> {
+        // fail instead of sharing the name if someone else already created the pipe
+        let server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(format!("{PIPE_PREFIX}{name}"))?;
+        server.connect().await?;
+
+        Ok(Box::new(server))
+    }
+
+    async fn connect(&self, name: &str) -> Result<Box<dyn Stream>> {
+        let pipe_name = format!("{PIPE_PREFIX}{name}");
+        let client = loop {
+            if let Ok(client) = ClientOptions::new().open(&pipe_name) {
+                break client;
+            }
+
+            time::sleep(Duration::from_millis(50)).await;
+        };
+
+        Ok(Box::new(client))
+    }
+}
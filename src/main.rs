@@ -1,11 +1,7 @@
-use std::collections::HashMap;
-
 use reqwest::{Client, Url};
 use tokio::runtime::Runtime;
 
-use anyhow::{anyhow, Result};
-
-use windows::Win32::System::Console::GetConsoleProcessList;
+use anyhow::{anyhow, ensure, Result};
 
 use flexi_logger::{Duplicate, FileSpec, Logger};
 use log::*;
@@ -16,6 +12,7 @@ mod dump;
 mod interfaces;
 mod ipc;
 mod reg;
+mod session;
 mod wilma;
 
 const DEFAULT_LOGGER_LEVEL: LevelFilter = if cfg!(debug_assertions) {
@@ -70,57 +67,133 @@ fn init_logger(discriminant: &str, level: Option<&str>) -> Result<flexi_logger::
     Ok(handle)
 }
 
+async fn exchange_token(
+    protocol_url: Url,
+    state: String,
+    client_id: String,
+    token_url: String,
+    code_verifier: String,
+) -> Result<wilma::auth::TokenData> {
+    wilma::auth::verify_state(&protocol_url, &state)?;
+
+    let client = get_client()?;
+
+    let token_url = Url::parse(token_url.as_str())?;
+
+    trace!("Starting token request");
+    wilma::auth::oauth_authenticate(
+        &client,
+        protocol_url,
+        token_url,
+        client_id,
+        code_verifier,
+        wilma::auth::WILMA_REDIRECT_URI,
+    )
+    .await
+}
+
 //TODO move elsewhere? Maybe wilma::auth?
 async fn handle_oauth(args: Vec<String>) -> Result<()> {
-    let protocol_url = Url::parse(args.get(2).expect("Missing protocol url").as_str())?;
-    assert!(protocol_url.scheme() == "wilma", "Invalid protocol url");
+    let protocol_url = Url::parse(args.get(2).ok_or_else(|| anyhow!("Missing protocol url"))?)?;
+    ensure!(protocol_url.scheme() == "wilma", "Invalid protocol url");
+
+    // the state picks which waiting login attempt this redirect belongs to
+    let state = wilma::auth::get_state(&protocol_url).ok_or_else(|| anyhow!("State missing"))?;
+    let channel = ipc::Channel::open(&state)?;
 
-    let data = ipc::receive_data().await?;
+    // from here on the waiting process gets an answer, even if reading its request fails
+    let connection = ipc::accept(&channel).await?;
 
-    match data {
-        ipc::IPCMessage::TokenRequest {
+    let result = match connection.receive().await {
+        Ok(ipc::IPCMessage::TokenRequest {
             state,
             client_id,
-            token_endpoint: token_url,
+            token_endpoint,
             code_verifier,
-        } => {
-            let params: HashMap<String, String> = protocol_url
-                .query_pairs()
-                .map(|(a, b)| (a.into_owned(), b.into_owned()))
-                .collect();
-            match params.get("state") {
-                Some(given) => {
-                    if *given != state {
-                        return Err(anyhow!("State mismatch"));
-                    }
-                }
-                None => return Err(anyhow!("State missing")),
-            }
-
-            let client = get_client()?;
-
-            let token_url = Url::parse(token_url.as_str())?;
-
-            trace!("Starting token request");
-            let token_data = wilma::auth::oauth_authenticate(
-                &client,
+        }) => {
+            exchange_token(
                 protocol_url,
-                token_url,
+                state,
                 client_id,
+                token_endpoint,
                 code_verifier,
             )
-            .await?;
+            .await
+        }
+        Ok(_) => Err(anyhow!("Unexpected IPC message")),
+        Err(e) => Err(e),
+    };
 
-            ipc::send_data(ipc::IPCMessage::TokenResponse {
-                access_token: token_data.access_token,
-                id_token: token_data.id_token,
-            })
+    match result {
+        Ok(token_data) => {
+            ipc::send_reply(
+                &channel,
+                ipc::IPCMessage::TokenResponse {
+                    access_token: token_data.access_token,
+                    id_token: token_data.id_token,
+                },
+            )
+            .await
+        }
+        Err(e) => {
+            ipc::send_reply(
+                &channel,
+                ipc::IPCMessage::Error {
+                    message: format!("{e:#}"),
+                },
+            )
             .await?;
+            Err(e)
         }
-        _ => unreachable!(),
     }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum InterfaceKind {
+    Cli,
+    Gui,
+}
+
+/// A console with only us attached means the exe was started from explorer
+#[cfg(windows)]
+fn launched_from_terminal() -> bool {
+    use windows::Win32::System::Console::GetConsoleProcessList;
+
+    unsafe {
+        let parents = GetConsoleProcessList(&mut [0]);
+        parents > 1
+    }
+}
 
-    Ok(())
+#[cfg(not(windows))]
+fn launched_from_terminal() -> bool {
+    use std::io::IsTerminal;
+
+    std::io::stdin().is_terminal()
+}
+
+#[cfg(windows)]
+fn has_display() -> bool {
+    true
+}
+
+#[cfg(not(windows))]
+fn has_display() -> bool {
+    cfg!(target_os = "macos")
+        || std::env::var_os("DISPLAY").is_some()
+        || std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+/// Explicit `--gui`/`--cli` first, then `FORCE_GUI`, then the GUI only when there is a display
+/// and no terminal
+fn select_interface(requested_gui: Option<bool>, from_terminal: bool) -> InterfaceKind {
+    match requested_gui {
+        Some(true) => InterfaceKind::Gui,
+        Some(false) => InterfaceKind::Cli,
+        None if std::env::var("FORCE_GUI").is_ok() => InterfaceKind::Gui,
+        None if from_terminal || !has_display() => InterfaceKind::Cli,
+        None => InterfaceKind::Gui,
+    }
 }
 
 fn run_interface(interface: impl Interface) -> Result<()> {
@@ -134,9 +207,15 @@ fn main() -> Result<()> {
         init_logger("oauth", Some("trace"))?;
 
         debug!("Called with __OAUTH, assuming from protocol handler");
-        let res = Runtime::new()
-            .expect("Failed to create runtime")
-            .block_on(handle_oauth(args));
+        // don't linger forever if the main process is gone
+        let res =
+            Runtime::new()
+                .expect("Failed to create runtime")
+                .block_on(wilma::auth::abortable(
+                    handle_oauth(args),
+                    wilma::auth::DEFAULT_LOGIN_TIMEOUT,
+                    std::future::pending(),
+                ));
 
         if res.is_err() {
             error!("Oauth handler failed: {:?}", res);
@@ -145,32 +224,29 @@ fn main() -> Result<()> {
         return res;
     }
 
-    let has_parent = unsafe {
-        let parents = GetConsoleProcessList(&mut [0]);
-        parents > 1
-    };
+    let from_terminal = launched_from_terminal();
+    let interface = select_interface(interfaces::requested_gui(), from_terminal);
 
-    let logger_discr = if has_parent { "no-terminal" } else { "" };
+    let logger_discr = if from_terminal { "no-terminal" } else { "" };
     init_logger(logger_discr, None)?;
 
-    debug!("Registering protocol handler");
-    reg::register_wilma_handler()?;
-
+    let _handler = reg::HandlerGuard;
     let rt = Runtime::new().expect("Failed to create runtime");
     let _guard = rt.enter();
 
-    let res = if has_parent && std::env::var("FORCE_GUI").is_err() {
-        debug!("Starting CLI interface");
-        run_interface(interfaces::CliInterface::new(rt.handle().clone()))
-    } else {
-        debug!("Starting Gui interface");
-        run_interface(interfaces::GuiInterface::new(rt.handle().clone()))
+    let res = match interface {
+        InterfaceKind::Cli => {
+            debug!("Starting CLI interface");
+            run_interface(interfaces::CliInterface::new(rt.handle().clone()))
+        }
+        InterfaceKind::Gui => {
+            debug!("Starting Gui interface");
+            run_interface(interfaces::GuiInterface::new(rt.handle().clone()))
+        }
     };
 
     debug!("Shutting down runtime");
     rt.shutdown_background();
-    debug!("Unregistering protocol handler");
-    reg::unregister_wilma_handler()?;
 
     res
 }
@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{anyhow, Result};
+
+use log::*;
+
+#[cfg(windows)]
+mod windows;
+#[cfg(unix)]
+mod xdg;
+
+#[cfg(windows)]
+use windows::process_alive;
+#[cfg(windows)]
+pub use windows::{get_registration, register_wilma_handler, unregister_wilma_handler};
+#[cfg(unix)]
+use xdg::process_alive;
+#[cfg(unix)]
+pub use xdg::{get_registration, register_wilma_handler, unregister_wilma_handler};
+
+/// Whether this process made the current per-run registration and should remove it
+static OWNS_REGISTRATION: AtomicBool = AtomicBool::new(false);
+/// Whether this process left a marker in `get_users_dir`
+static USES_REGISTRATION: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug)]
+pub struct Registration {
+    /// Command the handler runs
+    pub command: String,
+    /// Installed with `install-handler` instead of for a single run
+    pub persistent: bool,
+    /// Runs this executable
+    pub current: bool,
+}
+
+/// Calls `release_handler` when dropped, so panics and early returns don't leave a per-run
+/// registration behind
+pub struct HandlerGuard;
+
+impl Drop for HandlerGuard {
+    fn drop(&mut self) {
+        release_handler();
+    }
+}
+
+/// Every process relying on a per-run registration leaves a marker named after its pid here, so
+/// the one that made it knows not to remove it from under another running instance
+fn get_users_dir() -> Result<PathBuf> {
+    let dir = dirs::runtime_dir()
+        .or_else(dirs::data_local_dir)
+        .ok_or_else(|| anyhow!("Could not find a directory for protocol handler users"))?
+        .join(env!("CARGO_PKG_NAME"))
+        .join("handler-users");
+    fs::create_dir_all(&dir)?;
+
+    Ok(dir)
+}
+
+fn add_user() -> Result<()> {
+    fs::write(get_users_dir()?.join(process::id().to_string()), "")?;
+    USES_REGISTRATION.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+fn remove_user() {
+    if !USES_REGISTRATION.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    if let Err(e) = get_users_dir().and_then(|dir| {
+        fs::remove_file(dir.join(process::id().to_string()))?;
+        Ok(())
+    }) {
+        warn!("Failed to remove protocol handler marker: {e:?}");
+    }
+}
+
+/// Whether another running instance relies on the per-run registration. Markers of processes
+/// that are gone are removed on the way
+fn has_other_users() -> Result<bool> {
+    let mut found = false;
+    for entry in fs::read_dir(get_users_dir()?)? {
+        let entry = entry?;
+        let pid = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok());
+        match pid {
+            Some(pid) if pid == process::id() => {}
+            Some(pid) if process_alive(pid) => found = true,
+            _ => {
+                trace!(
+                    "Removing stale protocol handler marker {:?}",
+                    entry.file_name()
+                );
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/// Registers the protocol handler for the rest of this run unless a persistent one or one from
+/// another running instance is already in place. Only a registration made here is removed again,
+/// by `release_handler` on exit
+pub fn ensure_handler() -> Result<()> {
+    if OWNS_REGISTRATION.load(Ordering::SeqCst) || USES_REGISTRATION.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    match get_registration()? {
+        Some(r) if r.persistent && r.current => debug!("Using installed protocol handler"),
+        Some(r) if r.persistent => warn!(
+            "Installed protocol handler runs {}, run install-handler again to update it",
+            r.command
+        ),
+        Some(r) if r.current && has_other_users()? => {
+            debug!("Protocol handler already registered by another running instance");
+            add_user()?;
+        }
+        //nothing registered, another program, or a per-run one nobody relies on anymore
+        _ => {
+            debug!("Registering protocol handler");
+            register_wilma_handler(false)?;
+            OWNS_REGISTRATION.store(true, Ordering::SeqCst);
+            add_user()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the per-run registration if this process made it, it hasn't been replaced by a
+/// persistent one since and no other running instance relies on it. One left for another
+/// instance is taken over by the next run after that instance is gone. Safe to call more than
+/// once, for exit paths that skip destructors
+pub fn release_handler() {
+    let owned = OWNS_REGISTRATION.swap(false, Ordering::SeqCst);
+    remove_user();
+    if !owned {
+        return;
+    }
+
+    let result = get_registration().and_then(|registration| match registration {
+        Some(r) if !r.persistent && r.current => {
+            if has_other_users()? {
+                debug!("Leaving protocol handler registered for another running instance");
+                return Ok(());
+            }
+            debug!("Unregistering protocol handler");
+            unregister_wilma_handler()
+        }
+        _ => Ok(()),
+    });
+
+    if let Err(e) = result {
+        error!("Failed to unregister protocol handler: {e:?}");
+    }
+}
@@ -0,0 +1,83 @@
+use std::io;
+
+use winreg::{enums::*, RegKey};
+
+use anyhow::{anyhow, Result};
+
+use super::Registration;
+
+const WILMA_KEY: &str = "SOFTWARE\\Classes\\wilma";
+const PERSISTENT_VALUE: &str = "WilmaDumperPersistent";
+
+fn get_command() -> Result<String> {
+    let path = std::env::current_exe()?.into_os_string();
+    let path = path
+        .to_str()
+        .ok_or_else(|| anyhow!("Path contains non-unicode characters"))?
+        .replace(r"\\?\", "");
+
+    Ok(format!(r#""{}" "__OAUTH" "%1""#, path))
+}
+
+pub fn register_wilma_handler(persistent: bool) -> Result<()> {
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let classes = hkcu.open_subkey("SOFTWARE\\Classes")?;
+
+    let wilma = classes.create_subkey("wilma")?.0;
+    wilma.set_value("", &"URL:wilma")?;
+    wilma.set_value("URL Protocol", &"")?;
+    wilma.set_value(PERSISTENT_VALUE, &u32::from(persistent))?;
+
+    let command = wilma.create_subkey("shell\\open\\command")?.0;
+    command.set_value("", &get_command()?)?;
+
+    Ok(())
+}
+
+pub fn unregister_wilma_handler() -> Result<()> {
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    match hkcu.delete_subkey_all(WILMA_KEY) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn get_registration() -> Result<Option<Registration>> {
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let wilma = match hkcu.open_subkey(WILMA_KEY) {
+        Ok(wilma) => wilma,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let command: String = match wilma.open_subkey("shell\\open\\command") {
+        Ok(command) => command.get_value("")?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let persistent: u32 = wilma.get_value(PERSISTENT_VALUE).unwrap_or(0);
+
+    Ok(Some(Registration {
+        current: command == get_command()?,
+        command,
+        persistent: persistent != 0,
+    }))
+}
+
+pub fn process_alive(pid: u32) -> bool {
+    use ::windows::Win32::Foundation::{CloseHandle, STILL_ACTIVE};
+    use ::windows::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    unsafe {
+        let Ok(process) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) else {
+            return false;
+        };
+        let mut code = 0;
+        let queried = GetExitCodeProcess(process, &mut code).as_bool();
+        CloseHandle(process);
+        !queried || code == STILL_ACTIVE.0 as u32
+    }
+}
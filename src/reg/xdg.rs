@@ -0,0 +1,451 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, Result};
+
+use log::*;
+
+use super::Registration;
+
+const DESKTOP_FILE: &str = "wilma-dumper.desktop";
+const MIME_TYPE: &str = "x-scheme-handler/wilma";
+const PERSISTENT_KEY: &str = "X-Wilma-Dumper-Persistent";
+/// mimeapps.list sections registering had to create, only these are dropped again
+const CREATED_SECTIONS_KEY: &str = "X-Wilma-Dumper-Created-Sections";
+const MIMEAPPS_SECTIONS: [&str; 2] = ["[Default Applications]", "[Added Associations]"];
+
+/// `$XDG_DATA_HOME`, usually `~/.local/share`
+fn get_data_home() -> Result<PathBuf> {
+    dirs::data_dir().ok_or_else(|| anyhow!("Could not find data directory"))
+}
+
+/// `$XDG_CONFIG_HOME`, usually `~/.config`
+fn get_config_home() -> Result<PathBuf> {
+    dirs::config_dir().ok_or_else(|| anyhow!("Could not find config directory"))
+}
+
+/// Quotes an Exec argument as described in the desktop entry spec, including the escaping of
+/// the string value itself and of `%`, which would otherwise start a field code
+fn quote_exec_arg(arg: &str) -> String {
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for c in arg.chars() {
+        match c {
+            '%' => quoted.push_str("%%"),
+            '"' | '`' | '$' => {
+                quoted.push_str("\\\\");
+                quoted.push(c);
+            }
+            '\\' => quoted.push_str("\\\\\\\\"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn get_command(exe: &Path) -> Result<String> {
+    let exe = exe
+        .to_str()
+        .ok_or_else(|| anyhow!("Path contains non-unicode characters"))?;
+
+    Ok(format!("{} __OAUTH %u", quote_exec_arg(exe)))
+}
+
+fn desktop_entry(exe: &Path, persistent: bool, created_sections: &[&str]) -> Result<String> {
+    let created_sections = created_sections
+        .iter()
+        .map(|s| format!("{};", s.trim_matches(|c| c == '[' || c == ']')))
+        .collect::<String>();
+
+    Ok(format!(
+        "[Desktop Entry]\n\
+        Type=Application\n\
+        Name={}\n\
+        Exec={}\n\
+        MimeType={MIME_TYPE};\n\
+        NoDisplay=true\n\
+        Terminal=false\n\
+        {PERSISTENT_KEY}={persistent}\n\
+        {CREATED_SECTIONS_KEY}={created_sections}\n",
+        env!("CARGO_PKG_NAME"),
+        get_command(exe)?
+    ))
+}
+
+fn read_desktop_entry(data_home: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(data_home.join("applications").join(DESKTOP_FILE)) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn get_desktop_value(contents: &str, key: &str) -> Option<String> {
+    contents
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .find(|(k, _)| k.trim() == key)
+        .map(|(_, v)| v.trim().to_string())
+}
+
+/// The mimeapps.list sections an earlier registration created
+fn get_created_sections(data_home: &Path) -> Result<Vec<&'static str>> {
+    let created = read_desktop_entry(data_home)?
+        .and_then(|contents| get_desktop_value(&contents, CREATED_SECTIONS_KEY))
+        .unwrap_or_default();
+
+    Ok(MIMEAPPS_SECTIONS
+        .into_iter()
+        .filter(|section| {
+            created
+                .split(';')
+                .any(|s| format!("[{}]", s.trim()) == *section)
+        })
+        .collect())
+}
+
+/// Adds or removes our desktop file from the wilma handler lists in mimeapps.list, keeping
+/// everything else as is. Returns the new contents and the sections registering created.
+/// Unregistering drops the `created` sections again once they are empty
+fn update_mimeapps(
+    contents: &str,
+    register: bool,
+    created: &[&str],
+) -> (String, Vec<&'static str>) {
+    let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+    let mut created_now = vec![];
+
+    for section in MIMEAPPS_SECTIONS {
+        let start = lines.iter().position(|l| l.trim() == section);
+        let start = match (start, register) {
+            (Some(start), _) => start,
+            (None, true) => {
+                if matches!(lines.last(), Some(l) if !l.trim().is_empty()) {
+                    lines.push(String::new());
+                }
+                lines.push(section.to_string());
+                created_now.push(section);
+                lines.len() - 1
+            }
+            (None, false) => continue,
+        };
+        let end = lines[start + 1..]
+            .iter()
+            .position(|l| l.trim_start().starts_with('['))
+            .map_or(lines.len(), |i| start + 1 + i);
+
+        let entry = lines[start + 1..end]
+            .iter()
+            .position(|l| l.split('=').next().map(str::trim) == Some(MIME_TYPE))
+            .map(|i| start + 1 + i);
+
+        let mut handlers: Vec<String> = entry
+            .and_then(|i| lines[i].split_once('='))
+            .map(|(_, v)| {
+                v.split(';')
+                    .map(str::trim)
+                    .filter(|h| !h.is_empty() && *h != DESKTOP_FILE)
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if register {
+            handlers.insert(0, DESKTOP_FILE.to_string());
+        }
+
+        let line = format!("{MIME_TYPE}={};", handlers.join(";"));
+        match (entry, handlers.is_empty()) {
+            (Some(i), true) => {
+                lines.remove(i);
+            }
+            (Some(i), false) => lines[i] = line,
+            (None, true) => {}
+            (None, false) => lines.insert(start + 1, line),
+        }
+
+        let end = lines[start + 1..]
+            .iter()
+            .position(|l| l.trim_start().starts_with('['))
+            .map_or(lines.len(), |i| start + 1 + i);
+        let empty = lines[start + 1..end].iter().all(|l| l.trim().is_empty());
+        if !register && empty && created.contains(&section) {
+            lines.drain(start..end);
+            //the blank line registering put before the section, if nothing follows it anymore
+            if start == lines.len() && matches!(lines.last(), Some(l) if l.trim().is_empty()) {
+                lines.pop();
+            }
+        }
+    }
+
+    let mut contents = lines.join("\n");
+    if !contents.is_empty() {
+        contents.push('\n');
+    }
+    (contents, created_now)
+}
+
+fn update_mimeapps_file(
+    config_home: &Path,
+    register: bool,
+    created: &[&str],
+) -> Result<Vec<&'static str>> {
+    let path = config_home.join("mimeapps.list");
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !register => return Ok(vec![]),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let (contents, created_now) = update_mimeapps(&contents, register, created);
+    fs::create_dir_all(config_home)?;
+    fs::write(path, contents)?;
+
+    Ok(created_now)
+}
+
+/// Refreshes mimeinfo.cache for launchers that don't read mimeapps.list, not all systems have it
+fn update_desktop_database(applications: &Path) {
+    match Command::new("update-desktop-database")
+        .arg(applications)
+        .status()
+    {
+        Ok(status) if status.success() => {}
+        Ok(status) => debug!("update-desktop-database exited with {status}"),
+        Err(e) => debug!("Could not run update-desktop-database: {e}"),
+    }
+}
+
+pub fn register_handler(
+    data_home: &Path,
+    config_home: &Path,
+    exe: &Path,
+    persistent: bool,
+) -> Result<()> {
+    let applications = data_home.join("applications");
+    fs::create_dir_all(&applications)?;
+
+    //registering again keeps what an earlier registration created
+    let mut created = get_created_sections(data_home)?;
+    created.extend(update_mimeapps_file(config_home, true, &created)?);
+
+    let desktop_file = applications.join(DESKTOP_FILE);
+    trace!("Writing desktop entry to {desktop_file:?}");
+    fs::write(desktop_file, desktop_entry(exe, persistent, &created)?)?;
+
+    update_desktop_database(&applications);
+
+    Ok(())
+}
+
+pub fn unregister_handler(data_home: &Path, config_home: &Path) -> Result<()> {
+    let applications = data_home.join("applications");
+    let created = get_created_sections(data_home)?;
+
+    match fs::remove_file(applications.join(DESKTOP_FILE)) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    update_mimeapps_file(config_home, false, &created)?;
+    if applications.exists() {
+        update_desktop_database(&applications);
+    }
+
+    Ok(())
+}
+
+pub fn get_handler_registration(data_home: &Path, exe: &Path) -> Result<Option<Registration>> {
+    let contents = match read_desktop_entry(data_home)? {
+        Some(contents) => contents,
+        None => return Ok(None),
+    };
+
+    let get_value = |key: &str| get_desktop_value(&contents, key);
+
+    let command = get_value("Exec").ok_or_else(|| anyhow!("Desktop entry has no Exec"))?;
+
+    Ok(Some(Registration {
+        current: command == get_command(exe)?,
+        command,
+        persistent: get_value(PERSISTENT_KEY).as_deref() == Some("true"),
+    }))
+}
+
+pub fn register_wilma_handler(persistent: bool) -> Result<()> {
+    register_handler(
+        &get_data_home()?,
+        &get_config_home()?,
+        &std::env::current_exe()?,
+        persistent,
+    )
+}
+
+pub fn unregister_wilma_handler() -> Result<()> {
+    unregister_handler(&get_data_home()?, &get_config_home()?)
+}
+
+pub fn get_registration() -> Result<Option<Registration>> {
+    get_handler_registration(&get_data_home()?, &std::env::current_exe()?)
+}
+
+/// `kill -0` only checks that the process exists. If it can't be run the process is assumed to
+/// be alive, keeping a registration is the safer mistake
+pub fn process_alive(pid: u32) -> bool {
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .map_or(true, |status| status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh `$XDG_DATA_HOME` and `$XDG_CONFIG_HOME` under the system temp directory
+    struct TempHome(PathBuf);
+
+    impl TempHome {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "{}-{name}-{}",
+                env!("CARGO_PKG_NAME"),
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn data(&self) -> PathBuf {
+            self.0.join("data")
+        }
+
+        fn config(&self) -> PathBuf {
+            self.0.join("config")
+        }
+    }
+
+    impl Drop for TempHome {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn register_and_unregister() {
+        let home = TempHome::new("register");
+        let exe = Path::new("/opt/wilma dumper/100%/wilma-dumper");
+
+        register_handler(&home.data(), &home.config(), exe, false).unwrap();
+        // Registering again, e.g. install-handler after a per-run registration
+        register_handler(&home.data(), &home.config(), exe, true).unwrap();
+
+        let registration = get_handler_registration(&home.data(), exe)
+            .unwrap()
+            .unwrap();
+        assert!(registration.current);
+        assert!(registration.persistent);
+        assert_eq!(
+            registration.command,
+            r#""/opt/wilma dumper/100%%/wilma-dumper" __OAUTH %u"#
+        );
+
+        let mimeapps = fs::read_to_string(home.config().join("mimeapps.list")).unwrap();
+        assert_eq!(
+            mimeapps,
+            "[Default Applications]\n\
+            x-scheme-handler/wilma=wilma-dumper.desktop;\n\
+            \n\
+            [Added Associations]\n\
+            x-scheme-handler/wilma=wilma-dumper.desktop;\n"
+        );
+
+        let other_exe = Path::new("/usr/bin/wilma-dumper");
+        assert!(
+            !get_handler_registration(&home.data(), other_exe)
+                .unwrap()
+                .unwrap()
+                .current
+        );
+
+        unregister_handler(&home.data(), &home.config()).unwrap();
+
+        assert!(get_handler_registration(&home.data(), exe)
+            .unwrap()
+            .is_none());
+        // Both sections were made by registering, so nothing is left behind
+        let mimeapps = fs::read_to_string(home.config().join("mimeapps.list")).unwrap();
+        assert_eq!(mimeapps, "");
+    }
+
+    #[test]
+    fn unregister_without_registration() {
+        let home = TempHome::new("unregister");
+
+        unregister_handler(&home.data(), &home.config()).unwrap();
+
+        assert!(!home.config().join("mimeapps.list").exists());
+    }
+
+    #[test]
+    fn mimeapps_round_trip() {
+        let original = "[Default Applications]\n\
+            text/html=firefox.desktop;\n\
+            x-scheme-handler/wilma=other.desktop;\n\
+            \n\
+            [Added Associations]\n\
+            \n\
+            [Removed Associations]\n\
+            image/png=gimp.desktop;\n";
+
+        let (registered, created) = update_mimeapps(original, true, &[]);
+        assert!(registered.contains("x-scheme-handler/wilma=wilma-dumper.desktop;other.desktop;"));
+        assert!(registered
+            .contains("[Added Associations]\nx-scheme-handler/wilma=wilma-dumper.desktop;\n"));
+        assert!(created.is_empty());
+
+        assert_eq!(update_mimeapps(&registered, true, &[]).0, registered);
+        // Sections that were there before stay, even the empty one
+        assert_eq!(update_mimeapps(&registered, false, &[]).0, original);
+    }
+
+    #[test]
+    fn mimeapps_sections_created_by_registering_are_dropped() {
+        let original = "[Removed Associations]\nimage/png=gimp.desktop;\n";
+
+        let (registered, created) = update_mimeapps(original, true, &[]);
+        assert_eq!(created, MIMEAPPS_SECTIONS);
+        assert_eq!(update_mimeapps(&registered, false, &created).0, original);
+
+        // A section that got other entries meanwhile is kept
+        let edited = registered.replace(
+            "[Added Associations]\n",
+            "[Added Associations]\ntext/html=firefox.desktop;\n",
+        );
+        let (unregistered, _) = update_mimeapps(&edited, false, &created);
+        assert_eq!(
+            unregistered,
+            "[Removed Associations]\n\
+            image/png=gimp.desktop;\n\
+            \n\
+            [Added Associations]\n\
+            text/html=firefox.desktop;\n"
+        );
+    }
+
+    #[test]
+    fn process_alive_sees_running_and_finished_processes() {
+        assert!(process_alive(std::process::id()));
+
+        let mut child = Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        assert!(!process_alive(pid));
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{from_slice, to_vec};
+
+use log::*;
+
+use crate::wilma::{models::WilmaRole, Wilma};
+
+const SESSION_FILE: &str = "session.json";
+
+#[derive(Serialize, Deserialize)]
+struct Session {
+    base_url: String,
+    name: String,
+    sid: String,
+    role: WilmaRole,
+}
+
+fn get_path() -> Result<PathBuf> {
+    Ok(dirs::config_dir()
+        .ok_or_else(|| anyhow!("Could not find config directory"))?
+        .join(env!("CARGO_PKG_NAME"))
+        .join(SESSION_FILE))
+}
+
+/// Loads the saved session, if there is one. The session is not checked for validity
+pub fn load() -> Result<Option<Wilma>> {
+    let path = get_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    trace!("Loading session from {path:?}");
+    let session: Session = from_slice(&fs::read(path)?)?;
+
+    Ok(Some(Wilma::from_session(
+        Url::parse(&session.base_url)?,
+        session.name,
+        session.sid,
+        session.role,
+    )))
+}
+
+pub fn save(wilma: &Wilma) -> Result<()> {
+    let session = Session {
+        base_url: wilma.base_url.to_string(),
+        name: wilma.name.clone(),
+        sid: wilma.get_sid().ok_or_else(|| anyhow!("Not logged in"))?,
+        role: wilma
+            .role
+            .clone()
+            .ok_or_else(|| anyhow!("No role selected"))?,
+    };
+
+    let path = get_path()?;
+    fs::create_dir_all(path.parent().unwrap())?;
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    trace!("Saving session to {path:?}");
+    options.open(path)?.write_all(&to_vec(&session)?)?;
+
+    Ok(())
+}
+
+pub fn clear() -> Result<()> {
+    let path = get_path()?;
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use reqwest::Client;
+use scraper::{ElementRef, Html, Selector};
+
+use anyhow::{ensure, Result};
+
+use lazy_static::lazy_static;
+
+use crate::wilma::Wilma;
+
+use super::html::{non_empty, parse_course_code, parse_date, text};
+use super::models::{AttendanceMark, AttendanceMarkType};
+
+lazy_static! {
+    static ref TABLE_SELECTOR: Selector = Selector::parse("table").unwrap();
+    static ref HEADER_SELECTOR: Selector = Selector::parse("thead th").unwrap();
+    static ref ROW_SELECTOR: Selector = Selector::parse("tbody tr").unwrap();
+    static ref CELL_SELECTOR: Selector = Selector::parse("td").unwrap();
+}
+
+/// Column indexes of the mark table, found from the header texts
+struct Columns {
+    date: usize,
+    lesson: Option<usize>,
+    course: Option<usize>,
+    mark: usize,
+    entered_by: Option<usize>,
+}
+
+impl Columns {
+    fn find(table: ElementRef) -> Option<Self> {
+        let headers = table
+            .select(&HEADER_SELECTOR)
+            .map(|h| text(h).to_lowercase())
+            .collect::<Vec<_>>();
+        let find = |names: &[&str]| {
+            headers
+                .iter()
+                .position(|h| names.iter().any(|n| h.contains(n)))
+        };
+
+        Some(Columns {
+            date: find(&["päivä", "pvm"])?,
+            lesson: find(&["tunti", "klo"]),
+            course: find(&["kurssi", "ryhmä", "aine"]),
+            mark: find(&["merkintä", "tyyppi", "syy"])?,
+            entered_by: find(&["merkitsijä", "kirjaaja", "opettaja"]),
+        })
+    }
+}
+
+fn parse_row(row: ElementRef, columns: &Columns) -> Option<AttendanceMark> {
+    let cells = row.select(&CELL_SELECTOR).map(text).collect::<Vec<_>>();
+    let cell = |i: Option<usize>| i.and_then(|i| cells.get(i).cloned()).and_then(non_empty);
+
+    let course = cell(columns.course).unwrap_or_default();
+    let mark = cell(Some(columns.mark))?;
+
+    Some(AttendanceMark {
+        date: parse_date(cells.get(columns.date)?)?,
+        lesson: cell(columns.lesson),
+        course_code: parse_course_code(&course),
+        course,
+        mark_type: AttendanceMarkType::from(mark.as_str()),
+        mark,
+        entered_by: cell(columns.entered_by),
+    })
+}
+
+/// Every attendance mark of the role, oldest first
+pub async fn get_attendance(client: &Client, wilma: &Wilma) -> Result<Vec<AttendanceMark>> {
+    ensure!(wilma.is_logged_in(), "Not logged in");
+
+    //range -3 is the whole history instead of the current period
+    let html = wilma
+        .get(client, wilma.get_url()?.join("attendance/view?range=-3")?)
+        .await?
+        .text()
+        .await?;
+
+    let document = Html::parse_document(html.as_str());
+
+    let mut marks = document
+        .select(&TABLE_SELECTOR)
+        .filter_map(|table| Some((table, Columns::find(table)?)))
+        .flat_map(|(table, columns)| {
+            table
+                .select(&ROW_SELECTOR)
+                .filter_map(|row| parse_row(row, &columns))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    marks.sort_by_key(|m| m.date);
+
+    Ok(marks)
+}
@@ -11,7 +11,7 @@ use regex::Regex;
 
 use crate::wilma::Wilma;
 
-use super::models::{Course, CourseGrade};
+use super::models::{Course, CourseGrade, WilmaRoleType};
 
 lazy_static! {
     static ref COMPULSORY_REGEX: Regex = Regex::new(r"choicesCompulsoryTypes = (\[.*\]);").unwrap();
@@ -100,14 +100,16 @@ fn parse_course(element: ElementRef, compulsory: &[i32], selectable: &[i32]) ->
 
 pub async fn get_courses(client: &Client, wilma: &Wilma) -> Result<Vec<Course>> {
     ensure!(wilma.is_logged_in(), "Not logged in");
-
-    let html = client
-        .get(wilma.get_url()?.join("choices?langid=1")?)
-        .header(
-            "Cookie",
-            format!("Wilma2SID={};", wilma.sid.as_ref().unwrap()),
-        )
-        .send()
+    ensure!(
+        !matches!(
+            wilma.role.as_ref().map(|r| &r.type_),
+            Some(WilmaRoleType::Teacher)
+        ),
+        "Course choices are only available for students, use groups for teacher roles"
+    );
+
+    let html = wilma
+        .get(client, wilma.get_url()?.join("choices?langid=1")?)
         .await?
         .text()
         .await?;
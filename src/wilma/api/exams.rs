@@ -0,0 +1,84 @@
+use reqwest::Client;
+use scraper::{ElementRef, Html, Selector};
+
+use anyhow::{ensure, Result};
+
+use lazy_static::lazy_static;
+
+use crate::wilma::Wilma;
+
+use super::html::{non_empty, parse_course_code, parse_date, text};
+use super::models::Exam;
+
+lazy_static! {
+    static ref EXAM_TABLE_SELECTOR: Selector = Selector::parse("table.table-grey").unwrap();
+    static ref ROW_SELECTOR: Selector = Selector::parse("tr").unwrap();
+    static ref HEADER_SELECTOR: Selector = Selector::parse("th").unwrap();
+    static ref CELL_SELECTOR: Selector = Selector::parse("td").unwrap();
+}
+
+//every exam is its own table, the first row has the date and course and the rest are labeled
+fn parse_exam(table: ElementRef) -> Option<Exam> {
+    let mut rows = table.select(&ROW_SELECTOR);
+
+    let first = rows.next()?;
+    let date = parse_date(&text(first))?;
+    let course = text(first.select(&CELL_SELECTOR).next()?)
+        .trim_end_matches(": Koe")
+        .to_string();
+
+    let mut exam = Exam {
+        date,
+        course_code: parse_course_code(&course),
+        course,
+        topic: None,
+        teacher: None,
+        grade: None,
+        info: None,
+    };
+
+    for row in rows {
+        let (Some(label), Some(value)) = (
+            row.select(&HEADER_SELECTOR).next(),
+            row.select(&CELL_SELECTOR).next(),
+        ) else {
+            continue;
+        };
+        let value = non_empty(text(value));
+        match text(label).trim_end_matches(':').to_lowercase().as_str() {
+            "koealue" | "aihe" => exam.topic = value,
+            "opettaja" | "opettajat" => exam.teacher = value,
+            "arvosana" => exam.grade = value,
+            "lisätiedot" => exam.info = value,
+            _ => {}
+        }
+    }
+
+    Some(exam)
+}
+
+async fn get_exam_page(client: &Client, wilma: &Wilma, path: &str) -> Result<Vec<Exam>> {
+    let html = wilma
+        .get(client, wilma.get_url()?.join(path)?)
+        .await?
+        .text()
+        .await?;
+
+    let document = Html::parse_document(html.as_str());
+
+    Ok(document
+        .select(&EXAM_TABLE_SELECTOR)
+        .filter_map(parse_exam)
+        .collect())
+}
+
+/// Both upcoming and past exams, oldest first
+pub async fn get_exams(client: &Client, wilma: &Wilma) -> Result<Vec<Exam>> {
+    ensure!(wilma.is_logged_in(), "Not logged in");
+
+    let mut exams = get_exam_page(client, wilma, "exams/calendar/past").await?;
+    exams.extend(get_exam_page(client, wilma, "exams/calendar").await?);
+    exams.sort_by_key(|e| e.date);
+
+    Ok(exams)
+}
@@ -0,0 +1,136 @@
+use chrono::NaiveDate;
+use reqwest::Client;
+use scraper::{Html, Selector};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use log::debug;
+
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use serde_json::from_slice;
+
+use crate::wilma::Wilma;
+
+use super::html::{non_empty, parse_course_code, text};
+use super::models::{GroupStudent, TeachingGroup, WilmaRoleType};
+
+lazy_static! {
+    static ref HEADER_SELECTOR: Selector = Selector::parse("table thead th").unwrap();
+    static ref ROW_SELECTOR: Selector = Selector::parse("table tbody tr").unwrap();
+    static ref CELL_SELECTOR: Selector = Selector::parse("td").unwrap();
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GroupsResponse {
+    #[serde(default)]
+    groups: Vec<GroupData>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GroupData {
+    id: i64,
+    name: String,
+    #[serde(default)]
+    course_code: Option<String>,
+    #[serde(default)]
+    course_name: Option<String>,
+    #[serde(default, alias = "TermName", alias = "Term")]
+    period: Option<String>,
+    #[serde(default)]
+    start_date: Option<NaiveDate>,
+    #[serde(default)]
+    end_date: Option<NaiveDate>,
+}
+
+/// Fails when the table has rows but no recognizable name column, guessing one would dump
+/// some other column as names
+fn parse_students(html: &str) -> Result<Vec<GroupStudent>> {
+    let document = Html::parse_document(html);
+    if document.select(&ROW_SELECTOR).next().is_none() {
+        return Ok(vec![]);
+    }
+
+    let headers = document
+        .select(&HEADER_SELECTOR)
+        .map(|h| text(h).to_lowercase())
+        .collect::<Vec<_>>();
+    let name_column = headers
+        .iter()
+        .position(|h| h.contains("nimi") || h.contains("opiskelija") || h.contains("oppilas"))
+        .ok_or_else(|| {
+            anyhow!(
+                "No name column in the student list ({})",
+                headers.join(", ")
+            )
+        })?;
+    let class_column = headers.iter().position(|h| h.contains("luokka"));
+
+    Ok(document
+        .select(&ROW_SELECTOR)
+        .filter_map(|row| {
+            let cells = row.select(&CELL_SELECTOR).map(text).collect::<Vec<_>>();
+            Some(GroupStudent {
+                name: cells.get(name_column).cloned().and_then(non_empty)?,
+                class: class_column
+                    .and_then(|i| cells.get(i).cloned())
+                    .and_then(non_empty),
+            })
+        })
+        .collect())
+}
+
+/// The teaching groups of a teacher role with their enrolled students
+pub async fn get_groups(client: &Client, wilma: &Wilma) -> Result<Vec<TeachingGroup>> {
+    ensure!(wilma.is_logged_in(), "Not logged in");
+    ensure!(
+        matches!(
+            wilma.role.as_ref().map(|r| &r.type_),
+            Some(WilmaRoleType::Teacher)
+        ),
+        "Teaching groups are only available for teacher roles"
+    );
+
+    let response: GroupsResponse = from_slice(
+        &wilma
+            .get(client, wilma.get_url()?.join("groups/index_json")?)
+            .await?
+            .bytes()
+            .await?,
+    )?;
+
+    let mut groups = vec![];
+    for group in response.groups {
+        debug!("Fetching students of group {}", group.name);
+        let html = wilma
+            .get(
+                client,
+                wilma
+                    .get_url()?
+                    .join(&format!("groups/{}/students", group.id))?,
+            )
+            .await?
+            .text()
+            .await?;
+
+        let students = parse_students(&html)
+            .with_context(|| format!("Invalid student list for group {}", group.name))?;
+        groups.push(TeachingGroup {
+            id: group.id,
+            course_code: group
+                .course_code
+                .filter(|c| !c.is_empty())
+                .or_else(|| parse_course_code(&group.name)),
+            code: group.name,
+            course: group.course_name.unwrap_or_default(),
+            period: group.period.and_then(non_empty),
+            start: group.start_date,
+            end: group.end_date,
+            students,
+        });
+    }
+    groups.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.code.cmp(&b.code)));
+
+    Ok(groups)
+}
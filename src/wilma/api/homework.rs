@@ -0,0 +1,117 @@
+use chrono::NaiveDate;
+use reqwest::Client;
+
+use anyhow::{ensure, Result};
+
+use serde::Deserialize;
+use serde_json::from_slice;
+
+use crate::wilma::Wilma;
+
+use super::html::parse_course_code;
+use super::models::{HomeworkEntry, HomeworkKind};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct OverviewResponse {
+    #[serde(default)]
+    groups: Vec<GroupData>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GroupData {
+    #[serde(default)]
+    course_code: Option<String>,
+    #[serde(default)]
+    course_name: Option<String>,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    teachers: Vec<TeacherData>,
+    #[serde(default)]
+    homework: Vec<HomeworkData>,
+    #[serde(default, alias = "LessonDiary")]
+    diary: Vec<DiaryData>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TeacherData {
+    teacher_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HomeworkData {
+    date: NaiveDate,
+    homework: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DiaryData {
+    date: NaiveDate,
+    #[serde(alias = "Text", alias = "Topic")]
+    note: String,
+    #[serde(default)]
+    teacher: Option<String>,
+}
+
+fn parse_group(group: GroupData) -> Vec<HomeworkEntry> {
+    let course_code = group
+        .course_code
+        .filter(|c| !c.is_empty())
+        .or_else(|| parse_course_code(&group.name));
+    let course = group.course_name.unwrap_or_default();
+    let teacher = group
+        .teachers
+        .into_iter()
+        .map(|t| t.teacher_name)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let teacher = (!teacher.is_empty()).then_some(teacher);
+
+    let entry = |date, kind, text, teacher| HomeworkEntry {
+        date,
+        kind,
+        course_code: course_code.clone(),
+        course: course.clone(),
+        group: group.name.clone(),
+        text,
+        teacher,
+    };
+
+    let homework = group
+        .homework
+        .into_iter()
+        .map(|h| entry(h.date, HomeworkKind::Homework, h.homework, teacher.clone()));
+    let diary = group.diary.into_iter().map(|d| {
+        let teacher = d.teacher.or_else(|| teacher.clone());
+        entry(d.date, HomeworkKind::Diary, d.note, teacher)
+    });
+
+    homework.chain(diary).collect()
+}
+
+/// Homework and lesson diary entries of every course group, oldest first
+pub async fn get_homework(client: &Client, wilma: &Wilma) -> Result<Vec<HomeworkEntry>> {
+    ensure!(wilma.is_logged_in(), "Not logged in");
+
+    let response: OverviewResponse = from_slice(
+        &wilma
+            .get(client, wilma.get_url()?.join("overview")?)
+            .await?
+            .bytes()
+            .await?,
+    )?;
+
+    let mut entries = response
+        .groups
+        .into_iter()
+        .flat_map(parse_group)
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.group.cmp(&b.group)));
+
+    Ok(entries)
+}
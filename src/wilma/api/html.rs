@@ -0,0 +1,56 @@
+//helpers shared by the pages that are scraped instead of read as json
+
+use chrono::NaiveDate;
+use scraper::ElementRef;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref DATE_REGEX: Regex = Regex::new(r"(\d{1,2})\.(\d{1,2})\.(\d{4})").unwrap();
+    static ref COURSE_CODE_REGEX: Regex = Regex::new(r"^([\p{L}\d]+?\d+)(?:\.\d+)?\b").unwrap();
+}
+
+/// Text content with whitespace collapsed
+pub fn text(element: ElementRef) -> String {
+    element
+        .text()
+        .flat_map(|t| t.split_whitespace())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Finds a Finnish d.m.yyyy date anywhere in the text
+pub fn parse_date(text: &str) -> Option<NaiveDate> {
+    let captures = DATE_REGEX.captures(text)?;
+    NaiveDate::from_ymd_opt(
+        captures[3].parse().ok()?,
+        captures[2].parse().ok()?,
+        captures[1].parse().ok()?,
+    )
+}
+
+/// Group captions look like "MAA02.3 Polynomifunktiot", the part before the group number is
+/// the same code courses use
+pub fn parse_course_code(course: &str) -> Option<String> {
+    Some(
+        COURSE_CODE_REGEX
+            .captures(course)?
+            .get(1)?
+            .as_str()
+            .to_string(),
+    )
+}
+
+/// Empty cells and placeholder dashes are missing values
+pub fn non_empty(text: String) -> Option<String> {
+    match text.as_str() {
+        "" | "-" => None,
+        _ => Some(text),
+    }
+}
+
+/// Links to files uploaded to wilma, only fetchable with the session cookie
+pub fn is_attachment_link(href: &str) -> bool {
+    href.contains("attachment") || href.contains("/files/")
+}
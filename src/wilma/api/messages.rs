@@ -0,0 +1,196 @@
+use chrono::NaiveDateTime;
+use reqwest::Client;
+
+use anyhow::{anyhow, ensure, Result};
+use log::{debug, warn};
+
+use serde::Deserialize;
+use serde_json::from_slice;
+
+use crate::wilma::{SessionExpired, Wilma, WilmaApi};
+
+use super::models::{Message, MessageFolder, MessageReply};
+
+#[derive(Deserialize)]
+struct MessageListResponse {
+    #[serde(rename = "Messages", default)]
+    messages: Vec<MessageData>,
+}
+
+#[derive(Deserialize)]
+struct MessageResponse {
+    #[serde(alias = "Messages")]
+    messages: Vec<MessageData>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MessageData {
+    id: i64,
+    #[serde(default)]
+    subject: String,
+    time_stamp: String,
+    #[serde(default)]
+    sender: Option<People>,
+    #[serde(default)]
+    senders: Option<People>,
+    #[serde(default)]
+    recipients: Option<People>,
+    content_html: Option<String>,
+    #[serde(default)]
+    reply_list: Vec<ReplyData>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ReplyData {
+    id: i64,
+    time_stamp: String,
+    #[serde(default)]
+    sender: Option<People>,
+    #[serde(default)]
+    content_html: String,
+}
+
+//depending on the endpoint people are either a display string or a list of objects
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum People {
+    Text(String),
+    List(Vec<Person>),
+}
+
+#[derive(Deserialize)]
+struct Person {
+    #[serde(alias = "Name", alias = "Caption", alias = "name")]
+    name: String,
+}
+
+impl People {
+    fn into_names(self) -> Vec<String> {
+        match self {
+            People::Text(s) if s.is_empty() => vec![],
+            People::Text(s) => vec![s],
+            People::List(people) => people.into_iter().map(|p| p.name).collect(),
+        }
+    }
+}
+
+fn parse_timestamp(timestamp: &str) -> Result<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S"))
+        .map_err(|e| anyhow!("Invalid timestamp {timestamp}: {e}"))
+}
+
+fn parse_message(data: MessageData, folder: MessageFolder) -> Result<Message> {
+    let sender = data
+        .sender
+        .or(data.senders)
+        .map(People::into_names)
+        .unwrap_or_default()
+        .join(", ");
+
+    let replies = data
+        .reply_list
+        .into_iter()
+        .map(|r| {
+            Ok(MessageReply {
+                id: r.id,
+                sender: r
+                    .sender
+                    .map(People::into_names)
+                    .unwrap_or_default()
+                    .join(", "),
+                timestamp: parse_timestamp(&r.time_stamp)?,
+                body_html: r.content_html,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Message {
+        id: data.id,
+        folder,
+        subject: data.subject,
+        sender,
+        recipients: data.recipients.map(People::into_names).unwrap_or_default(),
+        timestamp: parse_timestamp(&data.time_stamp)?,
+        body_html: data.content_html,
+        replies,
+    })
+}
+
+fn folder_path(folder: MessageFolder) -> &'static str {
+    match folder {
+        MessageFolder::Inbox => "messages/list",
+        MessageFolder::Sent => "messages/list/outbox",
+        MessageFolder::Archive => "messages/list/archive",
+    }
+}
+
+/// Lists a folder, the messages don't have a body or replies yet
+pub async fn get_messages(
+    client: &Client,
+    wilma: &Wilma,
+    folder: MessageFolder,
+) -> Result<Vec<Message>> {
+    ensure!(wilma.is_logged_in(), "Not logged in");
+
+    let mut url = wilma.get_url()?.join(folder_path(folder))?;
+    url.query_pairs_mut().append_pair("format", "json");
+
+    let response: MessageListResponse = from_slice(&wilma.get(client, url).await?.bytes().await?)?;
+
+    response
+        .messages
+        .into_iter()
+        .map(|m| parse_message(m, folder))
+        .collect()
+}
+
+/// Opens a single message with its body and the replies in its thread
+pub async fn get_message(
+    client: &Client,
+    wilma: &Wilma,
+    folder: MessageFolder,
+    id: i64,
+) -> Result<Message> {
+    ensure!(wilma.is_logged_in(), "Not logged in");
+
+    let mut url = wilma.get_url()?.join(&format!("messages/{id}"))?;
+    url.query_pairs_mut().append_pair("format", "json");
+
+    let response: MessageResponse = from_slice(&wilma.get(client, url).await?.bytes().await?)?;
+
+    let data = response
+        .messages
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Message {id} not found"))?;
+
+    parse_message(data, folder)
+}
+
+/// Lists the given folders and opens every message in them, messages that fail to open are
+/// skipped so one broken message doesn't stop an archive
+pub async fn get_full_messages(
+    client: &Client,
+    wilma: &Wilma,
+    folders: &[MessageFolder],
+) -> Result<Vec<Message>> {
+    let mut messages = vec![];
+    for folder in folders {
+        for message in wilma.get_messages(client, *folder).await? {
+            debug!("Opening message {} from {folder:?}", message.id);
+            match wilma.get_message(client, *folder, message.id).await {
+                Ok(message) => messages.push(message),
+                Err(e) if e.is::<SessionExpired>() => return Err(e),
+                Err(e) => warn!(
+                    "Skipping message {} ({}): {e:#}",
+                    message.id, message.subject
+                ),
+            }
+        }
+    }
+
+    Ok(messages)
+}
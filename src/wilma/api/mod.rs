@@ -1,16 +1,27 @@
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
 
 use async_trait::async_trait;
+use chrono::NaiveDate;
 
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{anyhow, ensure, Context, Result};
 use log::debug;
-use reqwest::Client;
+use reqwest::{Client, Response};
 use serde_json::{from_slice, to_string};
 
 use super::Wilma;
 
+pub mod attendance;
 pub mod courses;
+pub mod exams;
+pub mod groups;
+pub mod homework;
+pub(crate) mod html;
+pub mod messages;
 pub mod models;
+pub mod news;
+pub mod schedule;
+pub mod tray;
 
 #[async_trait]
 pub trait WilmaApi {
@@ -18,6 +29,43 @@ pub trait WilmaApi {
     async fn get_index_json(&self, client: &Client) -> Result<models::WilmaIndexJson>;
     async fn get_providers(&self, client: &Client) -> Result<Option<Vec<models::OpenIDProvider>>>;
     async fn get_courses(&self, client: &Client) -> Result<Vec<models::Course>>;
+    async fn get_schedule(
+        &self,
+        client: &Client,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Result<Vec<models::Lesson>>;
+    async fn get_messages(
+        &self,
+        client: &Client,
+        folder: models::MessageFolder,
+    ) -> Result<Vec<models::Message>>;
+    async fn get_exams(&self, client: &Client) -> Result<Vec<models::Exam>>;
+    async fn get_attendance(&self, client: &Client) -> Result<Vec<models::AttendanceMark>>;
+    async fn get_periods(
+        &self,
+        client: &Client,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Result<Vec<models::Period>>;
+    async fn get_homework(&self, client: &Client) -> Result<Vec<models::HomeworkEntry>>;
+    async fn get_groups(&self, client: &Client) -> Result<Vec<models::TeachingGroup>>;
+    async fn get_course_tray(&self, client: &Client) -> Result<Vec<models::CourseOffering>>;
+    async fn get_news_list(
+        &self,
+        client: &Client,
+        scope: models::NewsScope,
+    ) -> Result<Vec<models::NewsItem>>;
+    async fn get_news_item(
+        &self,
+        client: &Client,
+        scope: models::NewsScope,
+        id: i64,
+    ) -> Result<models::NewsItem>;
+    async fn get_message(
+        &self,
+        client: &Client,
+        folder: models::MessageFolder,
+        id: i64,
+    ) -> Result<models::Message>;
 
     async fn openid_login(
         &mut self,
@@ -27,6 +75,12 @@ pub trait WilmaApi {
         access_token: String,
         id_token: String,
     ) -> Result<()>;
+    async fn password_login(
+        &mut self,
+        client: &Client,
+        username: String,
+        password: String,
+    ) -> Result<()>;
     async fn get_roles(&self, client: &Client) -> Result<Vec<models::WilmaRole>>;
     fn set_role(&mut self, role: &models::WilmaRole) -> Result<()>;
 }
@@ -57,6 +111,87 @@ impl WilmaApi for Wilma {
         courses::get_courses(client, self).await
     }
 
+    async fn get_schedule(
+        &self,
+        client: &Client,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Result<Vec<models::Lesson>> {
+        ensure!(self.is_logged_in(), "Not logged in");
+        schedule::get_schedule(client, self, range).await
+    }
+
+    async fn get_messages(
+        &self,
+        client: &Client,
+        folder: models::MessageFolder,
+    ) -> Result<Vec<models::Message>> {
+        ensure!(self.is_logged_in(), "Not logged in");
+        messages::get_messages(client, self, folder).await
+    }
+
+    async fn get_message(
+        &self,
+        client: &Client,
+        folder: models::MessageFolder,
+        id: i64,
+    ) -> Result<models::Message> {
+        ensure!(self.is_logged_in(), "Not logged in");
+        messages::get_message(client, self, folder, id).await
+    }
+
+    async fn get_exams(&self, client: &Client) -> Result<Vec<models::Exam>> {
+        ensure!(self.is_logged_in(), "Not logged in");
+        exams::get_exams(client, self).await
+    }
+
+    async fn get_attendance(&self, client: &Client) -> Result<Vec<models::AttendanceMark>> {
+        ensure!(self.is_logged_in(), "Not logged in");
+        attendance::get_attendance(client, self).await
+    }
+
+    async fn get_periods(
+        &self,
+        client: &Client,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Result<Vec<models::Period>> {
+        ensure!(self.is_logged_in(), "Not logged in");
+        schedule::get_periods(client, self, range).await
+    }
+
+    async fn get_homework(&self, client: &Client) -> Result<Vec<models::HomeworkEntry>> {
+        ensure!(self.is_logged_in(), "Not logged in");
+        homework::get_homework(client, self).await
+    }
+
+    async fn get_groups(&self, client: &Client) -> Result<Vec<models::TeachingGroup>> {
+        ensure!(self.is_logged_in(), "Not logged in");
+        groups::get_groups(client, self).await
+    }
+
+    async fn get_course_tray(&self, client: &Client) -> Result<Vec<models::CourseOffering>> {
+        ensure!(self.is_logged_in(), "Not logged in");
+        tray::get_course_tray(client, self).await
+    }
+
+    async fn get_news_list(
+        &self,
+        client: &Client,
+        scope: models::NewsScope,
+    ) -> Result<Vec<models::NewsItem>> {
+        ensure!(self.is_logged_in(), "Not logged in");
+        news::get_news_list(client, self, scope).await
+    }
+
+    async fn get_news_item(
+        &self,
+        client: &Client,
+        scope: models::NewsScope,
+        id: i64,
+    ) -> Result<models::NewsItem> {
+        ensure!(self.is_logged_in(), "Not logged in");
+        news::get_news_item(client, self, scope, id).await
+    }
+
     async fn openid_login(
         &mut self,
         client: &Client,
@@ -80,42 +215,51 @@ impl WilmaApi for Wilma {
             .send()
             .await?;
 
-        let sid_header =
-            response
-                .headers()
-                .get_all("Set-Cookie")
-                .iter()
-                .find(|c| match c.to_str() {
-                    Ok(s) => s.starts_with("Wilma2SID="),
-                    Err(_) => false,
-                });
-
-        let sid = sid_header
-            .ok_or_else(|| anyhow!("No SID header"))?
-            .to_str()?
-            .split(';')
-            .next()
-            .ok_or_else(|| anyhow!("Malformed SID cookie"))?
-            .split_at(10)
-            .1;
-
-        self.sid = Some(sid.to_string());
+        self.set_sid(get_sid(&response)?);
 
         Ok(())
     }
 
-    async fn get_roles(&self, client: &Client) -> Result<Vec<models::WilmaRole>> {
-        ensure!(self.sid.is_some(), "Session ID not set");
+    async fn password_login(
+        &mut self,
+        client: &Client,
+        username: String,
+        password: String,
+    ) -> Result<()> {
+        let session_id = self.get_index_json(client).await?.session_id;
 
         let response = client
-            .get(self.base_url.join("/api/v1/accounts/me/roles")?)
-            .header(
-                "Cookie",
-                format!("Wilma2SID={};", self.sid.as_ref().unwrap()),
-            )
+            .post(self.base_url.join("/index_json")?)
+            .form(&[
+                ("Login", username.as_str()),
+                ("Password", password.as_str()),
+                ("SESSIONID", session_id.as_str()),
+                ("CompleteJson", ""),
+                ("format", "json"),
+            ])
             .send()
             .await?;
 
+        //a failed login can still set a cookie, only the result tells if it worked
+        let sid = get_sid(&response);
+        let result: models::WilmaLoginResponse =
+            from_slice(&response.bytes().await?).context("Invalid login response")?;
+        ensure!(
+            result.login_result == "Ok",
+            "Invalid username or password ({})",
+            result.login_result
+        );
+
+        self.set_sid(sid.context("Invalid username or password")?);
+
+        Ok(())
+    }
+
+    async fn get_roles(&self, client: &Client) -> Result<Vec<models::WilmaRole>> {
+        let response = self
+            .get(client, self.base_url.join("/api/v1/accounts/me/roles")?)
+            .await?;
+
         let response: models::WilmaRoleResponse = from_slice(&response.bytes().await?)?;
 
         Ok(response.payload)
@@ -127,3 +271,25 @@ impl WilmaApi for Wilma {
         Ok(())
     }
 }
+
+fn get_sid(response: &Response) -> Result<String> {
+    let sid_header = response
+        .headers()
+        .get_all("Set-Cookie")
+        .iter()
+        .find(|c| match c.to_str() {
+            Ok(s) => s.starts_with("Wilma2SID="),
+            Err(_) => false,
+        });
+
+    let sid = sid_header
+        .ok_or_else(|| anyhow!("No SID header"))?
+        .to_str()?
+        .split(';')
+        .next()
+        .ok_or_else(|| anyhow!("Malformed SID cookie"))?
+        .split_at(10)
+        .1;
+
+    Ok(sid.to_string())
+}
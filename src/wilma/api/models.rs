@@ -1,4 +1,5 @@
 use anyhow::anyhow;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Debug, Clone)]
@@ -84,12 +85,216 @@ impl Course {
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
-#[serde(from = "String")]
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Lesson {
+    #[serde(rename = "ReservationID")]
+    pub reservation_id: i64,
+    pub date: NaiveDate,
+    pub weekday: u32, //ISO 8601, monday is 1
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub subject: String,
+    pub course_codes: Vec<String>,
+    pub teachers: Vec<String>,
+    pub rooms: Vec<String>,
+    pub groups: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Exam {
+    pub date: NaiveDate,
+    pub course_code: Option<String>, //same as Course.code
+    pub course: String,
+    pub topic: Option<String>,
+    pub teacher: Option<String>,
+    pub grade: Option<String>, //only once published
+    pub info: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(into = "String")]
+pub enum AttendanceMarkType {
+    Excused,
+    Unexcused,
+    Late,
+    Other,
+}
+
+impl From<&str> for AttendanceMarkType {
+    fn from(mark: &str) -> Self {
+        let mark = mark.to_lowercase();
+        if mark.contains("myöh") {
+            Self::Late
+        } else if mark.contains("selvittämätön") || mark.contains("luvaton") {
+            Self::Unexcused
+        } else if mark.contains("selvitetty")
+            || mark.contains("luvallinen")
+            || mark.contains("sairaus")
+        {
+            Self::Excused
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl From<AttendanceMarkType> for String {
+    fn from(mark_type: AttendanceMarkType) -> Self {
+        match mark_type {
+            AttendanceMarkType::Excused => "excused".to_string(),
+            AttendanceMarkType::Unexcused => "unexcused".to_string(),
+            AttendanceMarkType::Late => "late".to_string(),
+            AttendanceMarkType::Other => "other".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct AttendanceMark {
+    pub date: NaiveDate,
+    pub lesson: Option<String>,
+    pub course_code: Option<String>, //same as Course.code
+    pub course: String,
+    pub mark_type: AttendanceMarkType,
+    pub mark: String, //the text shown in wilma
+    pub entered_by: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Period {
+    pub name: String,
+    #[serde(rename = "StartDate")]
+    pub start: NaiveDate,
+    #[serde(rename = "EndDate")]
+    pub end: NaiveDate,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomeworkKind {
+    Homework,
+    Diary,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct HomeworkEntry {
+    pub date: NaiveDate,
+    pub kind: HomeworkKind,
+    pub course_code: Option<String>, //same as Course.code
+    pub course: String,
+    pub group: String,
+    pub text: String,
+    pub teacher: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewsScope {
+    School,
+    Role,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct NewsItem {
+    pub id: i64,
+    pub scope: NewsScope,
+    pub title: String,
+    pub author: Option<String>,
+    pub published: Option<NaiveDate>,
+    pub body_html: Option<String>, //only present on fetched items
+    pub attachments: Vec<NewsAttachment>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct NewsAttachment {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct TeachingGroup {
+    pub id: i64,
+    pub code: String,
+    pub course_code: Option<String>, //same as Course.code
+    pub course: String,
+    pub period: Option<String>,
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    pub students: Vec<GroupStudent>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct GroupStudent {
+    pub name: String,
+    pub class: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnrolmentStatus {
+    Selected,
+    Available,
+    Full,
+    Closed,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct CourseOffering {
+    pub tray: String,
+    pub period: Option<String>,
+    pub bar: Option<String>,
+    pub group: String,
+    pub course_code: Option<String>, //same as Course.code
+    pub course: String,
+    pub teacher: Option<String>,
+    pub enrolled: Option<u32>,
+    pub capacity: Option<u32>,
+    pub status: EnrolmentStatus,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MessageFolder {
+    Inbox,
+    Sent,
+    Archive,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Message {
+    pub id: i64,
+    pub folder: MessageFolder,
+    pub subject: String,
+    pub sender: String,
+    pub recipients: Vec<String>,
+    pub timestamp: NaiveDateTime,
+    pub body_html: Option<String>, //only present on opened messages
+    pub replies: Vec<MessageReply>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct MessageReply {
+    pub id: i64,
+    pub sender: String,
+    pub timestamp: NaiveDateTime,
+    pub body_html: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(from = "String", into = "String")]
 pub enum WilmaRoleType {
     Passwd,
     Student,
     Teacher,
+    Guardian,
 
     Unknown = -1,
 }
@@ -100,17 +305,36 @@ impl From<String> for WilmaRoleType {
             "passwd" => Self::Passwd,
             "student" => Self::Student,
             "teacher" => Self::Teacher,
+            "guardian" => Self::Guardian,
             _ => Self::Unknown,
         }
     }
 }
 
+impl From<WilmaRoleType> for String {
+    fn from(role_type: WilmaRoleType) -> Self {
+        match role_type {
+            WilmaRoleType::Passwd => "passwd".to_string(),
+            WilmaRoleType::Student => "student".to_string(),
+            WilmaRoleType::Teacher => "teacher".to_string(),
+            WilmaRoleType::Guardian => "guardian".to_string(),
+            WilmaRoleType::Unknown => "unknown".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WilmaLoginResponse {
+    #[serde(rename = "LoginResult")]
+    pub login_result: String,
+}
+
 #[derive(Deserialize)]
 pub struct WilmaRoleResponse {
     pub payload: Vec<WilmaRole>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WilmaRole {
     pub name: String,
     #[serde(rename = "type")]
@@ -123,12 +347,13 @@ pub struct WilmaRole {
     pub schools: Vec<WilmaSchool>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WilmaSchool {
     pub id: i32,
     pub caption: String,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 pub struct WilmaIndexJson {
     #[serde(rename = "LoginResult")]
@@ -149,11 +374,33 @@ pub struct OpenIDProvider {
     pub scope: String,
 }
 
+/// OpenID Connect discovery document, see
+/// <https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata>
+#[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 pub struct OpenIDConfiguration {
     pub issuer: String,
     pub authorization_endpoint: String,
     pub token_endpoint: String,
+    pub jwks_uri: Option<String>,
+    pub userinfo_endpoint: Option<String>,
+    pub end_session_endpoint: Option<String>,
+    pub registration_endpoint: Option<String>,
+    pub revocation_endpoint: Option<String>,
+    pub introspection_endpoint: Option<String>,
+
+    pub scopes_supported: Option<Vec<String>>,
+    #[serde(default)]
+    pub response_types_supported: Vec<String>,
+    pub response_modes_supported: Option<Vec<String>>,
+    pub grant_types_supported: Option<Vec<String>>,
+    #[serde(default)]
+    pub subject_types_supported: Vec<String>,
+    #[serde(default)]
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Option<Vec<String>>,
+    pub claims_supported: Option<Vec<String>>,
+    pub code_challenge_methods_supported: Option<Vec<String>>,
 }
 
 #[derive(Deserialize, Debug)]
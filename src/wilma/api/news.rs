@@ -0,0 +1,171 @@
+use reqwest::{Client, Url};
+use scraper::{ElementRef, Html, Selector};
+
+use anyhow::{anyhow, ensure, Result};
+use log::{debug, warn};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::wilma::{SessionExpired, Wilma, WilmaApi};
+
+use super::html::{is_attachment_link, non_empty, parse_date, text};
+use super::models::{NewsAttachment, NewsItem, NewsScope};
+
+lazy_static! {
+    static ref NEWS_ID_REGEX: Regex = Regex::new(r"news/(\d+)").unwrap();
+    static ref LINK_SELECTOR: Selector = Selector::parse("a[href]").unwrap();
+    static ref CONTENT_SELECTOR: Selector =
+        Selector::parse("#news-content, .news-item, #main-content").unwrap();
+    static ref TITLE_SELECTOR: Selector = Selector::parse("h1, h2").unwrap();
+    static ref META_SELECTOR: Selector = Selector::parse(".small, .date, .author").unwrap();
+    static ref AUTHOR_SELECTOR: Selector = Selector::parse("a, .author").unwrap();
+}
+
+fn news_url(wilma: &Wilma, scope: NewsScope) -> Result<Url> {
+    Ok(match scope {
+        NewsScope::School => wilma.base_url.join("/news/")?,
+        NewsScope::Role => wilma.get_url()?.join("news/")?,
+    })
+}
+
+fn parse_content(content: ElementRef, item: &mut NewsItem, base: &Url) {
+    if let Some(title) = content.select(&TITLE_SELECTOR).next() {
+        item.title = text(title);
+    }
+
+    if let Some(meta) = content.select(&META_SELECTOR).next() {
+        item.published = parse_date(&text(meta)).or(item.published);
+        item.author = meta
+            .select(&AUTHOR_SELECTOR)
+            .next()
+            .map(text)
+            .and_then(non_empty)
+            .or_else(|| item.author.take());
+    }
+
+    item.attachments = content
+        .select(&LINK_SELECTOR)
+        .filter_map(|a| {
+            let href = a.value().attr("href")?;
+            if !is_attachment_link(href) {
+                return None;
+            }
+            Some(NewsAttachment {
+                name: non_empty(text(a)).unwrap_or_else(|| href.to_string()),
+                url: base.join(href).ok()?.to_string(),
+            })
+        })
+        .collect();
+
+    item.body_html = Some(content.inner_html());
+}
+
+/// Lists the news of a scope, the items don't have a body yet
+pub async fn get_news_list(
+    client: &Client,
+    wilma: &Wilma,
+    scope: NewsScope,
+) -> Result<Vec<NewsItem>> {
+    ensure!(wilma.is_logged_in(), "Not logged in");
+
+    let html = wilma
+        .get(client, news_url(wilma, scope)?)
+        .await?
+        .text()
+        .await?;
+
+    let document = Html::parse_document(html.as_str());
+
+    let mut items: Vec<NewsItem> = vec![];
+    for link in document.select(&LINK_SELECTOR) {
+        let Some(id) = NEWS_ID_REGEX
+            .captures(link.value().attr("href").unwrap_or_default())
+            .and_then(|c| c[1].parse().ok())
+        else {
+            continue;
+        };
+        let Some(title) = non_empty(text(link)) else {
+            continue;
+        };
+        if items.iter().any(|i| i.id == id) {
+            continue;
+        }
+
+        //the date is usually in the surrounding list element
+        let published = link
+            .parent()
+            .and_then(ElementRef::wrap)
+            .and_then(|p| parse_date(&text(p)));
+
+        items.push(NewsItem {
+            id,
+            scope,
+            title,
+            author: None,
+            published,
+            body_html: None,
+            attachments: vec![],
+        });
+    }
+
+    Ok(items)
+}
+
+/// Fetches a single news item with its body and attachment links
+pub async fn get_news_item(
+    client: &Client,
+    wilma: &Wilma,
+    scope: NewsScope,
+    id: i64,
+) -> Result<NewsItem> {
+    ensure!(wilma.is_logged_in(), "Not logged in");
+
+    let url = news_url(wilma, scope)?.join(&id.to_string())?;
+    let html = wilma.get(client, url.clone()).await?.text().await?;
+
+    let document = Html::parse_document(html.as_str());
+    let content = document
+        .select(&CONTENT_SELECTOR)
+        .next()
+        .ok_or_else(|| anyhow!("News item {id} has no content"))?;
+
+    let mut item = NewsItem {
+        id,
+        scope,
+        title: String::new(),
+        author: None,
+        published: None,
+        body_html: None,
+        attachments: vec![],
+    };
+    parse_content(content, &mut item, &url);
+
+    Ok(item)
+}
+
+/// Lists both school-wide and role news and fetches every item
+pub async fn get_full_news(client: &Client, wilma: &Wilma) -> Result<Vec<NewsItem>> {
+    let mut items = vec![];
+    for scope in [NewsScope::School, NewsScope::Role] {
+        for listed in wilma.get_news_list(client, scope).await? {
+            debug!("Fetching news item {} from {scope:?}", listed.id);
+            let mut item = match wilma.get_news_item(client, scope, listed.id).await {
+                Ok(item) => item,
+                Err(e) if e.is::<SessionExpired>() => return Err(e),
+                Err(e) => {
+                    warn!("Skipping news item {} ({}): {e:#}", listed.id, listed.title);
+                    continue;
+                }
+            };
+            if item.title.is_empty() {
+                item.title = listed.title;
+            }
+            item.published = item.published.or(listed.published);
+            items.push(item);
+        }
+    }
+    items.sort_by_key(|i| i.published);
+
+    Ok(items)
+}
@@ -0,0 +1,205 @@
+use std::ops::RangeInclusive;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime};
+use reqwest::{Client, Url};
+
+use anyhow::{anyhow, ensure, Result};
+
+use serde::Deserialize;
+use serde_json::from_slice;
+
+use crate::wilma::Wilma;
+
+use super::models::{Lesson, Period, WilmaRoleType};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ScheduleResponse {
+    schedule: Vec<ScheduleEntry>,
+    #[serde(default)]
+    terms: Vec<Period>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ScheduleEntry {
+    #[serde(rename = "ReservationID")]
+    reservation_id: i64,
+    start: String,
+    end: String,
+    #[serde(default)]
+    groups: Vec<ScheduleGroup>,
+    #[serde(default)]
+    date_array: Vec<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ScheduleGroup {
+    #[serde(default)]
+    caption: String,
+    full_caption: Option<String>,
+    short_caption: Option<String>,
+    #[serde(default)]
+    teachers: Vec<ScheduleResource>,
+    #[serde(default)]
+    rooms: Vec<ScheduleResource>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ScheduleResource {
+    caption: String,
+    long_caption: Option<String>,
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M").map_err(|e| anyhow!("Invalid time {time}: {e}"))
+}
+
+fn push_unique(list: &mut Vec<String>, value: String) {
+    if !value.is_empty() && !list.contains(&value) {
+        list.push(value);
+    }
+}
+
+fn parse_entry(entry: ScheduleEntry, range: &RangeInclusive<NaiveDate>) -> Result<Vec<Lesson>> {
+    let start = parse_time(&entry.start)?;
+    let end = parse_time(&entry.end)?;
+
+    let (mut subjects, mut course_codes, mut teachers, mut rooms, mut groups) =
+        (vec![], vec![], vec![], vec![], vec![]);
+    for group in entry.groups {
+        push_unique(
+            &mut subjects,
+            group.full_caption.unwrap_or_else(|| group.caption.clone()),
+        );
+        push_unique(&mut course_codes, group.caption.clone());
+        push_unique(&mut groups, group.short_caption.unwrap_or(group.caption));
+        for teacher in group.teachers {
+            push_unique(
+                &mut teachers,
+                teacher.long_caption.unwrap_or(teacher.caption),
+            );
+        }
+        for room in group.rooms {
+            push_unique(&mut rooms, room.long_caption.unwrap_or(room.caption));
+        }
+    }
+
+    Ok(entry
+        .date_array
+        .into_iter()
+        .filter(|date| range.contains(date))
+        .map(|date| Lesson {
+            reservation_id: entry.reservation_id,
+            date,
+            weekday: date.weekday().number_from_monday(),
+            start,
+            end,
+            subject: subjects.join(", "),
+            course_codes: course_codes.clone(),
+            teachers: teachers.clone(),
+            rooms: rooms.clone(),
+            groups: groups.clone(),
+        })
+        .collect())
+}
+
+/// Monday to sunday of the week containing `date`
+pub fn week_of(date: NaiveDate) -> RangeInclusive<NaiveDate> {
+    let monday = date - Duration::days(date.weekday().num_days_from_monday().into());
+    monday..=monday + Duration::days(6)
+}
+
+fn get_schedule_url(wilma: &Wilma) -> Result<Url> {
+    let role = wilma
+        .role
+        .as_ref()
+        .ok_or_else(|| anyhow!("No role selected"))?;
+    let kind = match role.type_ {
+        WilmaRoleType::Teacher => "teachers",
+        _ => "students",
+    };
+
+    Ok(wilma
+        .get_url()?
+        .join(&format!("schedule/export/{kind}/{}/", role.primus_id))?)
+}
+
+async fn get_week(client: &Client, wilma: &Wilma, date: NaiveDate) -> Result<ScheduleResponse> {
+    let mut url = get_schedule_url(wilma)?;
+    url.query_pairs_mut()
+        .append_pair("date", &date.format("%-d.%-m.%Y").to_string())
+        .append_pair("getfullweek", "true");
+
+    Ok(from_slice(&wilma.get(client, url).await?.bytes().await?)?)
+}
+
+pub async fn get_schedule(
+    client: &Client,
+    wilma: &Wilma,
+    range: RangeInclusive<NaiveDate>,
+) -> Result<Vec<Lesson>> {
+    ensure!(wilma.is_logged_in(), "Not logged in");
+    ensure!(range.start() <= range.end(), "Invalid date range");
+
+    //the endpoint returns the whole week containing the given date
+    let mut week = *week_of(*range.start()).start();
+    let mut lessons = vec![];
+    while week <= *range.end() {
+        for entry in get_week(client, wilma, week).await?.schedule {
+            lessons.extend(parse_entry(entry, &range)?);
+        }
+
+        week += Duration::days(7);
+    }
+
+    lessons.sort_by_key(|l| (l.date, l.start, l.reservation_id));
+    lessons.dedup_by_key(|l| (l.date, l.reservation_id));
+
+    Ok(lessons)
+}
+
+/// First day of the school year after the one `date` is in, school years start in August
+fn next_school_year(date: NaiveDate) -> NaiveDate {
+    let year = if date.month() >= 8 {
+        date.year() + 1
+    } else {
+        date.year()
+    };
+    NaiveDate::from_ymd_opt(year, 8, 1).unwrap()
+}
+
+/// The periods (terms) of every school year that overlaps `range`, as listed with the schedule.
+/// Each schedule week only lists the terms of its own school year
+pub async fn get_periods(
+    client: &Client,
+    wilma: &Wilma,
+    range: RangeInclusive<NaiveDate>,
+) -> Result<Vec<Period>> {
+    ensure!(wilma.is_logged_in(), "Not logged in");
+
+    let mut periods: Vec<Period> = vec![];
+    let mut date = *range.start();
+    while date <= *range.end() {
+        let terms = get_week(client, wilma, date).await?.terms;
+
+        date = match terms.iter().map(|t| t.end).max() {
+            Some(end) if end >= date => end + Duration::days(1),
+            _ => next_school_year(date),
+        };
+
+        for term in terms {
+            if !periods
+                .iter()
+                .any(|p| p.start == term.start && p.end == term.end)
+            {
+                periods.push(term);
+            }
+        }
+    }
+    periods.sort_by_key(|p| p.start);
+
+    Ok(periods)
+}
@@ -0,0 +1,150 @@
+use reqwest::Client;
+use scraper::{ElementRef, Html, Selector};
+
+use anyhow::{ensure, Result};
+use log::debug;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::wilma::Wilma;
+
+use super::html::{non_empty, parse_course_code, text};
+use super::models::{CourseOffering, EnrolmentStatus};
+
+lazy_static! {
+    static ref TRAY_ID_REGEX: Regex = Regex::new(r"selection/(\d+)").unwrap();
+    static ref PERIOD_REGEX: Regex = Regex::new(r"(?i)\d+\.?\s*(jakso|periodi?)").unwrap();
+    static ref BAR_REGEX: Regex = Regex::new(r"(?i)\d+\.?\s*palkki|palkki\s*\d+").unwrap();
+    static ref SEATS_REGEX: Regex = Regex::new(r"^(\d+)\s*/\s*(\d+)$").unwrap();
+    static ref LINK_SELECTOR: Selector = Selector::parse("a[href]").unwrap();
+    static ref GROUP_SELECTOR: Selector = Selector::parse("[data-group-id]").unwrap();
+    static ref HEADING_SELECTOR: Selector =
+        Selector::parse("h1, h2, h3, h4, h5, .caption").unwrap();
+    static ref CODE_SELECTOR: Selector = Selector::parse("strong, b").unwrap();
+    static ref TEACHER_SELECTOR: Selector = Selector::parse(".teacher, .teachers").unwrap();
+    static ref NAME_SELECTOR: Selector = Selector::parse(".name, .course-name").unwrap();
+    static ref SEATS_SELECTOR: Selector = Selector::parse(".seats, .count").unwrap();
+}
+
+/// The closest heading of the ancestors that matches, periods and bars are nested sections
+/// with their name as the first heading
+fn find_section(element: ElementRef, regex: &Regex) -> Option<String> {
+    element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .find_map(|a| {
+            let heading = text(a.select(&HEADING_SELECTOR).next()?);
+            Some(regex.find(&heading)?.as_str().to_string())
+        })
+}
+
+fn parse_status(group: ElementRef, seats: Option<(u32, u32)>) -> EnrolmentStatus {
+    let classes = group
+        .value()
+        .classes()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    let has = |names: &[&str]| classes.iter().any(|c| names.contains(&c.as_str()));
+
+    if has(&["sel", "selected", "own"]) {
+        EnrolmentStatus::Selected
+    } else if has(&["locked", "closed", "disabled"]) {
+        EnrolmentStatus::Closed
+    } else if has(&["full"]) || matches!(seats, Some((enrolled, capacity)) if enrolled >= capacity)
+    {
+        EnrolmentStatus::Full
+    } else {
+        EnrolmentStatus::Available
+    }
+}
+
+fn parse_group(group: ElementRef, tray: &str) -> Option<CourseOffering> {
+    let code = group
+        .select(&CODE_SELECTOR)
+        .next()
+        .map(text)
+        .and_then(non_empty)
+        .or_else(|| text(group).split_whitespace().next().map(str::to_string))?;
+
+    //only the seat element, other text like dates can look like "1/2" too
+    let seats = group
+        .select(&SEATS_SELECTOR)
+        .next()
+        .map(text)
+        .and_then(|seats| {
+            let c = SEATS_REGEX.captures(seats.trim())?;
+            Some((c[1].parse().ok()?, c[2].parse().ok()?))
+        });
+
+    //the title holds the full course name, the visible name is usually shortened
+    let course = group
+        .value()
+        .attr("title")
+        .map(str::to_string)
+        .and_then(non_empty)
+        .or_else(|| group.select(&NAME_SELECTOR).next().map(text))
+        .and_then(non_empty)
+        .unwrap_or_default();
+
+    Some(CourseOffering {
+        tray: tray.to_string(),
+        period: find_section(group, &PERIOD_REGEX),
+        bar: find_section(group, &BAR_REGEX),
+        course_code: parse_course_code(&code),
+        group: code,
+        course,
+        teacher: group
+            .value()
+            .attr("data-teacher")
+            .map(str::to_string)
+            .or_else(|| group.select(&TEACHER_SELECTOR).next().map(text))
+            .and_then(non_empty),
+        enrolled: seats.map(|(enrolled, _)| enrolled),
+        capacity: seats.map(|(_, capacity)| capacity),
+        status: parse_status(group, seats),
+    })
+}
+
+/// Every group in every course tray the role can see
+pub async fn get_course_tray(client: &Client, wilma: &Wilma) -> Result<Vec<CourseOffering>> {
+    ensure!(wilma.is_logged_in(), "Not logged in");
+
+    let html = wilma
+        .get(client, wilma.get_url()?.join("selection/view")?)
+        .await?
+        .text()
+        .await?;
+
+    let mut trays: Vec<(u64, String)> = vec![];
+    for link in Html::parse_document(html.as_str()).select(&LINK_SELECTOR) {
+        let Some(id) = TRAY_ID_REGEX
+            .captures(link.value().attr("href").unwrap_or_default())
+            .and_then(|c| c[1].parse().ok())
+        else {
+            continue;
+        };
+        if !trays.iter().any(|(i, _)| *i == id) {
+            trays.push((id, text(link)));
+        }
+    }
+
+    let mut offerings = vec![];
+    for (id, name) in trays {
+        debug!("Fetching course tray {id} {name}");
+        let html = wilma
+            .get(client, wilma.get_url()?.join(&format!("selection/{id}"))?)
+            .await?
+            .text()
+            .await?;
+
+        let document = Html::parse_document(html.as_str());
+        offerings.extend(
+            document
+                .select(&GROUP_SELECTOR)
+                .filter_map(|g| parse_group(g, &name)),
+        );
+    }
+
+    Ok(offerings)
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use reqwest::{Client, Url};
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use lazy_static::lazy_static;
+
+use super::api::html::{is_attachment_link, non_empty, text};
+use super::models::{Message, NewsItem};
+use super::Wilma;
+
+pub const DEFAULT_DOWNLOAD_LIMIT: usize = 4;
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+lazy_static! {
+    static ref LINK_SELECTOR: Selector = Selector::parse("a[href]").unwrap();
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(tag = "Type", content = "Id")]
+pub enum AttachmentParent {
+    Message(i64),
+    News(i64),
+}
+
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub parent: AttachmentParent,
+    pub name: String,
+    pub url: Url,
+}
+
+/// Links a downloaded file back to the item it was attached to
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ManifestEntry {
+    pub parent: AttachmentParent,
+    pub name: String,
+    pub url: String,
+    pub file: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// Attachment links in message bodies and their replies
+pub fn message_attachments(messages: &[Message], base: &Url) -> Vec<Attachment> {
+    let mut attachments = vec![];
+    for message in messages {
+        let bodies = message
+            .body_html
+            .iter()
+            .chain(message.replies.iter().map(|r| &r.body_html));
+        for body in bodies {
+            let fragment = Html::parse_fragment(body);
+            for link in fragment.select(&LINK_SELECTOR) {
+                let href = link.value().attr("href").unwrap_or_default();
+                if !is_attachment_link(href) {
+                    continue;
+                }
+                let Ok(url) = base.join(href) else {
+                    continue;
+                };
+                attachments.push(Attachment {
+                    parent: AttachmentParent::Message(message.id),
+                    name: non_empty(text(link)).unwrap_or_else(|| file_name_from_url(&url)),
+                    url,
+                });
+            }
+        }
+    }
+    attachments
+}
+
+pub fn news_attachments(items: &[NewsItem]) -> Vec<Attachment> {
+    items
+        .iter()
+        .flat_map(|item| {
+            item.attachments.iter().filter_map(|a| {
+                Some(Attachment {
+                    parent: AttachmentParent::News(item.id),
+                    name: a.name.clone(),
+                    url: Url::parse(&a.url).ok()?,
+                })
+            })
+        })
+        .collect()
+}
+
+fn file_name_from_url(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut s| s.next_back())
+        .and_then(|s| non_empty(s.to_string()))
+        .unwrap_or_else(|| String::from("attachment"))
+}
+
+/// Keeps names readable but free of path separators and characters windows refuses
+pub fn safe_file_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    let name = name.trim().trim_matches('.');
+
+    let name = match name {
+        "" => String::from("attachment"),
+        name => name.chars().take(100).collect(),
+    };
+
+    //reserved device names can't be used even with an extension
+    let stem = name.split('.').next().unwrap_or_default().to_uppercase();
+    let reserved = matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || ((stem.starts_with("COM") || stem.starts_with("LPT"))
+            && stem.len() == 4
+            && stem.ends_with(|c: char| c.is_ascii_digit()));
+    match reserved {
+        true => format!("_{name}"),
+        false => name,
+    }
+}
+
+/// Downloads attachments into one directory with a manifest, shared between runs
+///
+/// Files with the same content are stored once no matter which item or run they came from.
+pub struct Downloader {
+    dir: PathBuf,
+    limit: usize,
+}
+
+impl Downloader {
+    pub fn new(dir: impl Into<PathBuf>, limit: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            limit: limit.max(1),
+        }
+    }
+
+    fn load_manifest(&self) -> Result<Vec<ManifestEntry>> {
+        match fs::read(self.dir.join(MANIFEST_NAME)) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save_manifest(&self, manifest: &[ManifestEntry]) -> Result<()> {
+        let tmp = self.dir.join(format!("{MANIFEST_NAME}.tmp"));
+        serde_json::to_writer_pretty(fs::File::create(&tmp)?, manifest)?;
+        fs::rename(tmp, self.dir.join(MANIFEST_NAME))?;
+        Ok(())
+    }
+
+    /// A name that isn't taken yet, "name (2).ext" style
+    fn unique_name(&self, name: &str) -> String {
+        let name = safe_file_name(name);
+        if !self.dir.join(&name).exists() {
+            return name;
+        }
+
+        let (stem, ext) = match name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{ext}")),
+            _ => (name.as_str(), String::new()),
+        };
+        (2..)
+            .map(|i| format!("{stem} ({i}){ext}"))
+            .find(|n| !self.dir.join(n).exists())
+            .unwrap()
+    }
+
+    /// Downloads the attachments that aren't in the manifest yet and returns the manifest
+    /// entries of all given attachments
+    ///
+    /// Failed downloads are logged and left out so one broken link doesn't stop an archive.
+    pub async fn download(
+        &self,
+        client: &Client,
+        wilma: &Wilma,
+        attachments: Vec<Attachment>,
+    ) -> Result<Vec<ManifestEntry>> {
+        fs::create_dir_all(&self.dir)?;
+        let mut manifest = self.load_manifest()?;
+
+        let mut entries = vec![];
+        let semaphore = Arc::new(Semaphore::new(self.limit));
+        let mut downloads = JoinSet::new();
+        for attachment in attachments {
+            let known = manifest.iter().find(|e| {
+                e.parent == attachment.parent
+                    && e.url == attachment.url.as_str()
+                    && self.dir.join(&e.file).exists()
+            });
+            if let Some(entry) = known {
+                entries.push(entry.clone());
+                continue;
+            }
+
+            //the session cookie must never leave wilma, not even to plain http or another port
+            if attachment.url.origin() != wilma.base_url.origin() {
+                warn!("Skipping attachment outside wilma: {}", attachment.url);
+                continue;
+            }
+
+            let (client, wilma, semaphore) = (client.clone(), wilma.clone(), semaphore.clone());
+            downloads.spawn(async move {
+                let _permit = semaphore.acquire_owned().await?;
+                debug!("Downloading {}", attachment.url);
+                let bytes = wilma
+                    .get(&client, attachment.url.clone())
+                    .await?
+                    .error_for_status()?
+                    .bytes()
+                    .await?;
+                Ok::<_, anyhow::Error>((attachment, bytes))
+            });
+        }
+
+        let mut by_hash = manifest
+            .iter()
+            .filter(|e| self.dir.join(&e.file).exists())
+            .map(|e| (e.sha256.clone(), e.file.clone()))
+            .collect::<HashMap<_, _>>();
+
+        let stored: Result<()> = async {
+            while let Some(result) = downloads.join_next().await {
+                let (attachment, bytes) = match result.map_err(|e| anyhow!(e)).and_then(|r| r) {
+                    Ok(download) => download,
+                    Err(e) => {
+                        warn!("Attachment download failed: {e}");
+                        continue;
+                    }
+                };
+
+                let sha256 = Sha256::digest(&bytes)
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect::<String>();
+                let file = match by_hash.get(&sha256) {
+                    Some(file) => file.clone(),
+                    None => {
+                        let file = self.unique_name(&attachment.name);
+                        fs::write(self.dir.join(&file), &bytes)?;
+                        by_hash.insert(sha256.clone(), file.clone());
+                        file
+                    }
+                };
+
+                let entry = ManifestEntry {
+                    parent: attachment.parent,
+                    name: attachment.name,
+                    url: attachment.url.to_string(),
+                    file,
+                    sha256,
+                    size: bytes.len() as u64,
+                };
+                manifest.retain(|e| !(e.parent == entry.parent && e.url == entry.url));
+                manifest.push(entry.clone());
+                entries.push(entry);
+            }
+            Ok(())
+        }
+        .await;
+
+        //files written before a failure must still be in the manifest, or they'd be stored
+        //again under a new name next time
+        self.save_manifest(&manifest)?;
+        stored?;
+
+        Ok(entries)
+    }
+}
@@ -1,5 +1,11 @@
+use std::fmt::{self, Debug, Display};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+
 use anyhow::{anyhow, Result};
-use reqwest::{Client, Url};
+use log::*;
+use reqwest::{Client, Response, StatusCode, Url};
 use serde::Deserialize;
 use serde_json::from_slice;
 
@@ -7,6 +13,7 @@ use api::models::WilmaHubWilma;
 
 pub mod api;
 pub mod auth;
+pub mod download;
 
 pub use api::models;
 pub use api::WilmaApi;
@@ -20,13 +27,51 @@ struct WilmaHubResponse {
     wilmat: Vec<WilmaHubWilma>,
 }
 
+/// Returned from requests when Wilma no longer accepts the session id
+#[derive(Debug)]
+pub struct SessionExpired;
+
+impl Display for SessionExpired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Wilma session has expired")
+    }
+}
+
+impl std::error::Error for SessionExpired {}
+
+pub type ReloginFuture = Pin<Box<dyn Future<Output = Result<Wilma>> + Send>>;
+
+/// Called with a copy of the expired wilma, should return it logged in again
+#[derive(Clone)]
+pub struct ReloginCallback(Arc<dyn Fn(Wilma) -> ReloginFuture + Send + Sync>);
+
+impl ReloginCallback {
+    pub fn new(callback: impl Fn(Wilma) -> ReloginFuture + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    pub fn call(&self, wilma: Wilma) -> ReloginFuture {
+        (self.0)(wilma)
+    }
+}
+
+impl Debug for ReloginCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ReloginCallback")
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Wilma {
     pub base_url: Url,
     pub name: String,
 
-    sid: Option<String>,
+    // shared between clones so a re-login is seen by all of them
+    sid: Arc<RwLock<Option<String>>>,
     pub role: Option<WilmaRole>,
+    relogin: Option<ReloginCallback>,
+    // held while logging in again so concurrent requests only do it once
+    relogin_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl Wilma {
@@ -34,8 +79,10 @@ impl Wilma {
         Self {
             base_url,
             name,
-            sid: None,
+            sid: Arc::default(),
             role: None,
+            relogin: None,
+            relogin_lock: Arc::default(),
         }
     }
 
@@ -43,12 +90,36 @@ impl Wilma {
         Self::new(base_url, String::new())
     }
 
+    pub fn from_session(base_url: Url, name: String, sid: String, role: WilmaRole) -> Self {
+        Self {
+            base_url,
+            name,
+            sid: Arc::new(RwLock::new(Some(sid))),
+            role: Some(role),
+            relogin: None,
+            relogin_lock: Arc::default(),
+        }
+    }
+
+    pub fn get_sid(&self) -> Option<String> {
+        self.sid.read().unwrap().clone()
+    }
+
+    /// Starts a new session, clones made before this keep the old one
+    fn set_sid(&mut self, sid: String) {
+        self.sid = Arc::new(RwLock::new(Some(sid)));
+    }
+
+    pub fn set_relogin(&mut self, callback: ReloginCallback) {
+        self.relogin = Some(callback);
+    }
+
     pub fn is_authenticated(&self) -> bool {
-        self.sid.is_some()
+        self.sid.read().unwrap().is_some()
     }
 
     pub fn is_logged_in(&self) -> bool {
-        self.sid.is_some() && self.role.is_some()
+        self.is_authenticated() && self.role.is_some()
     }
 
     pub fn get_url(&self) -> Result<Url> {
@@ -63,6 +134,70 @@ impl Wilma {
             .as_str(),
         )?)
     }
+
+    async fn send_get(&self, client: &Client, url: Url, sid: &str) -> Result<Response> {
+        let response = client
+            .get(url.clone())
+            .header("Cookie", format!("Wilma2SID={sid};"))
+            .send()
+            .await?;
+
+        // expired sessions get redirected to the front page or login form
+        let redirected_to_login =
+            response.url().path() != url.path() && matches!(response.url().path(), "/" | "/login");
+
+        if redirected_to_login
+            || matches!(
+                response.status(),
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+            )
+        {
+            return Err(SessionExpired.into());
+        }
+
+        Ok(response)
+    }
+
+    /// Authenticated GET request. If the session has expired and a re-login callback is set,
+    /// logs in again once and retries. Concurrent requests that hit the same expired session
+    /// wait for a single re-login
+    pub async fn get(&self, client: &Client, url: Url) -> Result<Response> {
+        let sid = self
+            .get_sid()
+            .ok_or_else(|| anyhow!("Session ID not set"))?;
+
+        match self.send_get(client, url.clone(), &sid).await {
+            Err(e) if e.is::<SessionExpired>() => {
+                let relogin = match &self.relogin {
+                    Some(relogin) => relogin,
+                    None => return Err(e),
+                };
+
+                let new_sid = {
+                    let _guard = self.relogin_lock.lock().await;
+
+                    match self.get_sid() {
+                        Some(current) if current != sid => {
+                            debug!("Session was renewed by another request");
+                            current
+                        }
+                        _ => {
+                            info!("Wilma session expired, logging in again");
+                            let wilma = relogin.call(self.clone()).await?;
+                            let new_sid = wilma
+                                .get_sid()
+                                .ok_or_else(|| anyhow!("Re-login did not set a session"))?;
+                            *self.sid.write().unwrap() = Some(new_sid.clone());
+                            new_sid
+                        }
+                    }
+                };
+
+                self.send_get(client, url, &new_sid).await
+            }
+            res => res,
+        }
+    }
 }
 
 pub async fn get_wilmas(client: &Client) -> Result<Vec<Wilma>> {